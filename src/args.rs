@@ -1,57 +1,224 @@
-use clap::Parser;
+use chrono::{DateTime, Local};
+use clap::{builder::RangedU64ValueParser, Parser, Subcommand};
+
+use crate::date_range::{parse_since, parse_until};
 
 /// Rename images with the date they were created
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 pub struct Args {
     /// Optional prefix for renamed images
+    #[arg(env = "CREATEDAT_NAME")]
     pub name: Option<String>,
 
     /// Choose which files to rename based on file extension
-    #[arg(short, long)]
+    #[arg(short, long, env = "CREATEDAT_EXTENSION")]
     pub extension: bool,
 
+    /// Only rename files with these extensions (e.g. 'jpg,png,tar.gz')
+    #[arg(
+        long,
+        value_name = "Extensions",
+        value_delimiter = ',',
+        env = "CREATEDAT_EXT"
+    )]
+    pub ext: Vec<String>,
+
+    /// Skip files with these extensions (e.g. 'mov,aae')
+    #[arg(
+        long,
+        value_name = "Extensions",
+        value_delimiter = ',',
+        env = "CREATEDAT_EXCLUDE_EXT"
+    )]
+    pub exclude_ext: Vec<String>,
+
+    /// Only rename files whose path matches this glob pattern (e.g. 'IMG_*')
+    #[arg(long, value_name = "Pattern", env = "CREATEDAT_INCLUDE")]
+    pub include: Vec<String>,
+
+    /// Skip files whose path matches this glob pattern (e.g. '*_edited.*')
+    #[arg(long, value_name = "Pattern", env = "CREATEDAT_EXCLUDE")]
+    pub exclude: Vec<String>,
+
+    /// Treat include and exclude patterns as regular expressions
+    #[arg(long, env = "CREATEDAT_REGEX")]
+    pub regex: bool,
+
+    /// Only rename files dated on or after this date ('2024-07-17', '2024-07-17 14:30' or '30d')
+    #[arg(long, value_name = "Date", value_parser = parse_since, env = "CREATEDAT_SINCE")]
+    pub since: Option<DateTime<Local>>,
+
+    /// Only rename files dated on or before this date ('2024-07-17', '2024-07-17 14:30' or '30d')
+    #[arg(long, value_name = "Date", value_parser = parse_until, env = "CREATEDAT_UNTIL")]
+    pub until: Option<DateTime<Local>>,
+
+    /// Replace extensions that don't match the file contents
+    #[arg(long, env = "CREATEDAT_FIX_EXTENSION")]
+    pub fix_extension: bool,
+
+    /// Rename sidecar files (.xmp, .aae, ...) on their own instead of with their file
+    #[arg(long, env = "CREATEDAT_NO_SIDECARS")]
+    pub no_sidecars: bool,
+
     /// Put date in front of filename
-    #[arg(short, long)]
+    #[arg(short, long, env = "CREATEDAT_FRONT")]
     pub front: bool,
 
     /// Remove original filename
-    #[arg(short, long)]
+    #[arg(short, long, env = "CREATEDAT_NO_NAME")]
     pub no_name: bool,
 
     /// Use 12-hour time format instead of 24-hour
-    #[arg(short, long)]
+    #[arg(short, long, env = "CREATEDAT_TWELVE")]
     pub twelve: bool,
 
     /// Date without time
-    #[arg(short, long)]
+    #[arg(short, long, env = "CREATEDAT_DATE")]
     pub date: bool,
 
     /// Use spaces instead of underscore under scores in name
-    #[arg(long)]
+    #[arg(long, env = "CREATEDAT_SPACE")]
     pub space: bool,
 
     /// Set custom date format to use ('%a %b %e %Y' = "Wed Jul 17 2024")
-    #[arg(long, value_name = "Format")]
+    #[arg(long, value_name = "Format", env = "CREATEDAT_FORMAT")]
     pub format: Option<String>,
 
+    /// Sort renamed files into folders by date ('%Y/%m' = 2024/07)
+    #[arg(long, value_name = "Format", env = "CREATEDAT_FOLDERS")]
+    pub folders: Option<String>,
+
+    /// Text for metadata placeholders like {model} the file has no value for (default: unknown)
+    #[arg(long, value_name = "Text", env = "CREATEDAT_MISSING")]
+    pub missing: Option<String>,
+
+    /// GeoNames cities file for {city} and {country} (default: bundled list of large cities)
+    #[arg(long, value_name = "Path", env = "CREATEDAT_PLACES")]
+    pub places: Option<String>,
+
     /// Set the source folder for images
-    #[arg(short = 'S', long, value_name = "Path")]
+    #[arg(short = 'S', long, value_name = "Path", env = "CREATEDAT_SOURCE")]
     pub source: Option<String>,
 
     /// Set the target folder for renamed images (default: renamed)
-    #[arg(short = 'T', long, value_name = "Path")]
+    #[arg(short = 'T', long, value_name = "Path", env = "CREATEDAT_TARGET")]
     pub target: Option<String>,
 
     /// Put custom name after the date
-    #[arg(short, long)]
+    #[arg(short, long, env = "CREATEDAT_SUFFIX")]
     pub suffix: bool,
 
     /// Preview the name format of renamed files
-    #[arg(short, long)]
+    #[arg(short, long, env = "CREATEDAT_PREVIEW")]
     pub preview: bool,
 
+    /// Number of files processed at once, 1 renames them one by one in name order (default: 15)
+    #[arg(
+        short,
+        long,
+        value_name = "N",
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+        env = "CREATEDAT_JOBS"
+    )]
+    pub jobs: Option<usize>,
+
+    /// Number of files read for their dates at once (default: jobs)
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+        env = "CREATEDAT_METADATA_JOBS"
+    )]
+    pub metadata_jobs: Option<usize>,
+
+    /// Number of files copied at once (default: jobs)
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+        env = "CREATEDAT_COPY_JOBS"
+    )]
+    pub copy_jobs: Option<usize>,
+
+    /// Rename images (default when no other type is chosen)
+    #[arg(long, env = "CREATEDAT_IMAGES")]
+    pub images: bool,
+
+    /// Rename videos
+    #[arg(long, env = "CREATEDAT_VIDEOS")]
+    pub videos: bool,
+
+    /// Rename audio files
+    #[arg(long, env = "CREATEDAT_AUDIO")]
+    pub audio: bool,
+
+    /// Rename documents (PDF, office files and plain text)
+    #[arg(long, env = "CREATEDAT_DOCUMENTS")]
+    pub documents: bool,
+
+    /// Rename archives (zip, tar, 7z, ...)
+    #[arg(long, env = "CREATEDAT_ARCHIVES")]
+    pub archives: bool,
+
     /// Rename all files, not just images
-    #[arg(short, long)]
+    #[arg(short, long, env = "CREATEDAT_ALL")]
     pub all: bool,
+
+    /// Use the options of a profile from the config files
+    #[arg(long, value_name = "Name", env = "CREATEDAT_PROFILE")]
+    pub profile: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Keep running and rename new files as they arrive in the source folder
+    Watch(WatchArgs),
+    /// Copy new files from a memory card into dated folders in the target folder
+    Import(ImportArgs),
+}
+
+#[derive(clap::Args)]
+pub struct WatchArgs {
+    /// Milliseconds a file's size has to stay the same before it is renamed
+    #[arg(
+        long,
+        value_name = "Ms",
+        default_value_t = 2000,
+        env = "CREATEDAT_WATCH_SETTLE"
+    )]
+    pub settle: u64,
+
+    /// Milliseconds to wait after a file was closed or moved in before renaming it
+    #[arg(
+        long,
+        value_name = "Ms",
+        default_value_t = 500,
+        env = "CREATEDAT_WATCH_DEBOUNCE"
+    )]
+    pub debounce: u64,
+}
+
+#[derive(clap::Args)]
+pub struct ImportArgs {
+    /// Mounted card, files are read from its DCIM folder when it has one
+    #[arg(value_name = "Path")]
+    pub card: String,
+
+    /// Recognize imported files by their contents instead of name, size and date
+    #[arg(long, env = "CREATEDAT_IMPORT_HASH")]
+    pub hash: bool,
+
+    // `--clear` and `--eject` have no environment variables, so a stray one can't delete
+    // the originals or eject the card
+    /// Delete imported files from the card once their copies are verified
+    #[arg(long)]
+    pub clear: bool,
+
+    /// Eject the card once all files are imported and verified
+    #[arg(long)]
+    pub eject: bool,
 }
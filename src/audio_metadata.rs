@@ -0,0 +1,316 @@
+use std::{
+    fs::{File, Metadata},
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+use crate::{
+    date_source::{Confidence, DateSource, FoundDate},
+    file_type::{sniff, FileType},
+    quicktime::{read_moov, user_data_value},
+    xmp,
+};
+
+/// Largest tag or comment block that is read into memory
+const MAX_TAG_LENGTH: u32 = 16 * 1024 * 1024;
+
+/// Bytes of an Ogg file searched for the comment header
+const OGG_LENGTH: u64 = 1024 * 1024;
+
+/// Where the origination date and time are in a `bext` chunk
+const BEXT_DATE: usize = 320;
+
+/// The recording date in the tags of MP3, FLAC, Ogg, WAV and M4A files
+///
+/// ID3v2 `TDRC` (or `TYER`, `TDAT` and `TIME` in older tags) is used before the original
+/// release date `TDOR`, Vorbis comments have a `DATE`, broadcast WAV files an
+/// origination date in their `bext` chunk and M4A files a `©day` item.
+/// Dates with just a year are ignored, they would all be named January 1st.
+pub struct AudioMetadata;
+
+impl DateSource for AudioMetadata {
+    fn name(&self) -> &str {
+        "audio metadata"
+    }
+
+    fn find_date(&self, path: &Path, _metadata: &Metadata, header: &[u8]) -> Option<FoundDate> {
+        if header.starts_with(b"ID3") {
+            return id3_date(path);
+        }
+        match sniff(header)? {
+            FileType::Flac => flac_date(path),
+            FileType::Ogg => ogg_date(path),
+            FileType::Wav => wav_date(path),
+            FileType::M4a => {
+                let moov = read_moov(&mut File::open(path).ok()?)?;
+                let day = user_data_value(&moov, b"\xA9day")?;
+                Some(FoundDate::new(
+                    parse_tag_date(std::str::from_utf8(day).ok()?)?,
+                    Confidence::High,
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn id3_date(path: &Path) -> Option<FoundDate> {
+    let mut file = File::open(path).ok()?;
+    let mut header = [0; 10];
+    file.read_exact(&mut header).ok()?;
+    let version = header[3];
+    let tag_end = 10 + u64::from(synchsafe(&header[6..10]));
+    // The extended header is skipped, its size is counted differently in each version
+    if header[5] & 0x40 != 0 && version >= 3 {
+        let mut size = [0; 4];
+        file.read_exact(&mut size).ok()?;
+        let size = if version == 4 {
+            synchsafe(&size)
+        } else {
+            u32::from_be_bytes(size) + 4
+        };
+        file.seek(SeekFrom::Start(10 + u64::from(size))).ok()?;
+    }
+
+    let mut frames = Id3Frames::default();
+    loop {
+        let position = file.stream_position().ok()?;
+        if position >= tag_end {
+            break;
+        }
+        // ID3v2.2 frames have three character IDs and sizes
+        let (id, size) = if version == 2 {
+            let mut frame = [0; 6];
+            file.read_exact(&mut frame).ok()?;
+            let size = u32::from_be_bytes([0, frame[3], frame[4], frame[5]]);
+            (frame[..3].to_vec(), size)
+        } else {
+            let mut frame = [0; 10];
+            file.read_exact(&mut frame).ok()?;
+            let size = if version == 4 {
+                synchsafe(&frame[4..8])
+            } else {
+                u32::from_be_bytes(frame[4..8].try_into().ok()?)
+            };
+            (frame[..4].to_vec(), size)
+        };
+        // Padding after the last frame
+        if id[0] == 0 {
+            break;
+        }
+        let text_frame = matches!(
+            id.as_slice(),
+            b"TDRC" | b"TDOR" | b"TYER" | b"TDAT" | b"TIME" | b"TYE" | b"TDA" | b"TIM"
+        );
+        if !text_frame || size > MAX_TAG_LENGTH {
+            file.seek(SeekFrom::Current(i64::from(size))).ok()?;
+            continue;
+        }
+        let mut data = vec![0; size as usize];
+        file.read_exact(&mut data).ok()?;
+        let text = id3_text(&data);
+        match id.as_slice() {
+            b"TDRC" => frames.recorded = text,
+            b"TDOR" => frames.original = text,
+            b"TYER" | b"TYE" => frames.year = text,
+            b"TDAT" | b"TDA" => frames.day = text,
+            _ => frames.time = text,
+        }
+    }
+    frames.date()
+}
+
+/// Text of the date frames in an ID3v2 tag
+#[derive(Default)]
+struct Id3Frames {
+    recorded: Option<String>,
+    original: Option<String>,
+    year: Option<String>,
+    /// `DDMM`
+    day: Option<String>,
+    /// `HHMM`
+    time: Option<String>,
+}
+
+impl Id3Frames {
+    fn date(&self) -> Option<FoundDate> {
+        if let Some(date) = self.recorded.as_deref().and_then(parse_tag_date) {
+            return Some(FoundDate::new(date, Confidence::High));
+        }
+        if let (Some(year), Some(day)) = (&self.year, &self.day) {
+            let time = self.time.as_deref().unwrap_or("0000");
+            let date = format!(
+                "{}{}{}{}00",
+                year.trim(),
+                &day.get(2..4)?,
+                &day.get(..2)?,
+                time
+            );
+            let date = NaiveDateTime::parse_from_str(&date, "%Y%m%d%H%M%S").ok()?;
+            return Some(FoundDate::new(local(date)?, Confidence::High));
+        }
+        let original = self.original.as_deref().and_then(parse_tag_date)?;
+        Some(FoundDate::new(original, Confidence::Medium))
+    }
+}
+
+/// Text frames start with their encoding and can hold several values separated by NULs
+fn id3_text(data: &[u8]) -> Option<String> {
+    let (&encoding, text) = data.split_first()?;
+    let text = match encoding {
+        1 | 2 => {
+            let big_endian = encoding == 2 || text.starts_with(&[0xFE, 0xFF]);
+            let units: Vec<u16> = text
+                .chunks_exact(2)
+                .map(|unit| {
+                    if big_endian {
+                        u16::from_be_bytes([unit[0], unit[1]])
+                    } else {
+                        u16::from_le_bytes([unit[0], unit[1]])
+                    }
+                })
+                .filter(|&unit| unit != 0xFEFF)
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(text).into_owned(),
+    };
+    let text = text.split('\0').next()?.trim();
+    (!text.is_empty()).then(|| text.to_owned())
+}
+
+fn synchsafe(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0, |size, &byte| (size << 7) | u32::from(byte & 0x7F))
+}
+
+fn flac_date(path: &Path) -> Option<FoundDate> {
+    let mut file = File::open(path).ok()?;
+    file.seek(SeekFrom::Start(4)).ok()?;
+    loop {
+        let mut header = [0; 4];
+        file.read_exact(&mut header).ok()?;
+        let last = header[0] & 0x80 != 0;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]);
+        // Block type 4 holds the Vorbis comments
+        if header[0] & 0x7F == 4 && length <= MAX_TAG_LENGTH {
+            let mut comments = vec![0; length as usize];
+            file.read_exact(&mut comments).ok()?;
+            return vorbis_comment_date(&comments);
+        }
+        if last {
+            return None;
+        }
+        file.seek(SeekFrom::Current(i64::from(length))).ok()?;
+    }
+}
+
+/// The comments are the second packet of the first stream in an Ogg file, after a
+/// `\x03vorbis` or `OpusTags` signature
+fn ogg_date(path: &Path) -> Option<FoundDate> {
+    let mut data = vec![];
+    File::open(path)
+        .ok()?
+        .take(OGG_LENGTH)
+        .read_to_end(&mut data)
+        .ok()?;
+    let mut packets: Vec<Vec<u8>> = vec![vec![]];
+    let mut position = 0;
+    while packets.len() < 3 {
+        let page = data.get(position..)?;
+        if !page.starts_with(b"OggS") {
+            return None;
+        }
+        let segments = usize::from(*page.get(26)?);
+        let table = page.get(27..27 + segments)?;
+        let mut offset = 27 + segments;
+        for &segment in table {
+            let segment = usize::from(segment);
+            packets
+                .last_mut()?
+                .extend_from_slice(page.get(offset..offset + segment)?);
+            offset += segment;
+            // Segments shorter than 255 bytes end a packet
+            if segment < 255 {
+                packets.push(vec![]);
+            }
+        }
+        position += offset;
+    }
+    let comments = &packets[1];
+    let comments = comments
+        .strip_prefix(b"\x03vorbis")
+        .or_else(|| comments.strip_prefix(b"OpusTags"))?;
+    vorbis_comment_date(comments)
+}
+
+fn vorbis_comment_date(comments: &[u8]) -> Option<FoundDate> {
+    let u32_at = |offset: usize| -> Option<usize> {
+        Some(u32::from_le_bytes(comments.get(offset..offset + 4)?.try_into().ok()?) as usize)
+    };
+    let mut offset = 4 + u32_at(0)?;
+    let count = u32_at(offset)?;
+    offset += 4;
+    for _ in 0..count {
+        let length = u32_at(offset)?;
+        let comment = comments.get(offset + 4..offset + 4 + length)?;
+        offset += 4 + length;
+        let comment = String::from_utf8_lossy(comment);
+        let Some((key, value)) = comment.split_once('=') else {
+            continue;
+        };
+        if key.eq_ignore_ascii_case("DATE") {
+            if let Some(date) = parse_tag_date(value) {
+                return Some(FoundDate::new(date, Confidence::High));
+            }
+        }
+    }
+    None
+}
+
+/// Broadcast WAV files have the origination date and time in their `bext` chunk
+fn wav_date(path: &Path) -> Option<FoundDate> {
+    let mut file = File::open(path).ok()?;
+    file.seek(SeekFrom::Start(12)).ok()?;
+    loop {
+        let mut header = [0; 8];
+        file.read_exact(&mut header).ok()?;
+        let length = u32::from_le_bytes(header[4..].try_into().ok()?);
+        if &header[..4] == b"bext" {
+            let mut origination = [0; 18];
+            file.seek(SeekFrom::Current(BEXT_DATE as i64)).ok()?;
+            file.read_exact(&mut origination).ok()?;
+            // `yyyy-mm-dd` and `hh-mm-ss`, with any separators
+            let digits: String = String::from_utf8_lossy(&origination)
+                .chars()
+                .filter(char::is_ascii_digit)
+                .collect();
+            let date = NaiveDateTime::parse_from_str(&digits, "%Y%m%d%H%M%S").ok()?;
+            return Some(FoundDate::new(local(date)?, Confidence::High));
+        }
+        // Chunks are padded to an even length
+        let padded = i64::from(length) + i64::from(length % 2);
+        file.seek(SeekFrom::Current(padded)).ok()?;
+    }
+}
+
+/// `2024-07-17T14:30:00Z`, `2024-07-17 14:30` or `2024-07-17`, but not just `2024`
+fn parse_tag_date(value: &str) -> Option<DateTime<FixedOffset>> {
+    let value = value.trim();
+    if value.len() < "2024-07-17".len() {
+        return None;
+    }
+    xmp::parse_date(value)
+        .or_else(|| xmp::parse_date(&value.replacen(' ', "T", 1)))
+        .or_else(|| {
+            let date = NaiveDate::parse_from_str(value.get(..10)?, "%Y-%m-%d").ok()?;
+            local(date.and_hms_opt(0, 0, 0)?)
+        })
+}
+
+fn local(date: NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+    Some(Local.from_local_datetime(&date).earliest()?.fixed_offset())
+}
@@ -0,0 +1,259 @@
+use std::{
+    collections::HashMap,
+    env,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::{parser::ValueSource, Arg, ArgAction, ArgMatches};
+use serde::Deserialize;
+
+use crate::{
+    args::{Args, Command},
+    date_range::{parse_since, parse_until},
+};
+
+/// Project-local config, read from the current folder
+pub const LOCAL_CONFIG: &str = ".createdat.toml";
+
+/// Defaults and named profiles from a config file
+///
+/// Top-level keys are the long option names, profiles are tables under `[profile.<name>]`.
+#[derive(Debug)]
+pub struct Config {
+    defaults: ConfigArgs,
+    profile: HashMap<String, ConfigArgs>,
+}
+
+impl Config {
+    fn read(path: &Path) -> Result<Option<Config>> {
+        let content = match read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+        };
+        Config::parse(&content)
+            .with_context(|| format!("parsing {}", path.display()))
+            .map(Some)
+    }
+
+    // Serde can't deny unknown fields through `flatten`, so profiles are split off by hand
+    fn parse(content: &str) -> Result<Config> {
+        let mut table: toml::Table = toml::from_str(content)?;
+        let profile = match table.remove("profile") {
+            Some(profile) => profile.try_into()?,
+            None => HashMap::new(),
+        };
+        Ok(Config {
+            defaults: toml::Value::Table(table).try_into()?,
+            profile,
+        })
+    }
+}
+
+/// `$XDG_CONFIG_HOME/createdat/config.toml`, or `~/.config/createdat/config.toml`
+pub fn global_config_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("createdat").join("config.toml"))
+}
+
+/// Fill in options that weren't given on the command line or in `CREATEDAT_*`
+/// environment variables from the config files
+///
+/// Later layers win: global defaults, local defaults, the global profile,
+/// the local profile, environment variables and finally the command line.
+pub fn apply(args: &mut Args, matches: &ArgMatches) -> Result<()> {
+    let configs: Vec<Config> = [global_config_path(), Some(PathBuf::from(LOCAL_CONFIG))]
+        .into_iter()
+        .flatten()
+        .map(|path| Config::read(&path))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let mut merged = ConfigArgs::default();
+    let mut profiles = Vec::new();
+    for mut config in configs {
+        if let Some(profile) = args
+            .profile
+            .as_ref()
+            .and_then(|name| config.profile.remove(name))
+        {
+            profiles.push(profile);
+        }
+        merged.merge(config.defaults);
+    }
+    if let Some(name) = &args.profile {
+        if profiles.is_empty() {
+            return Err(anyhow!("profile '{name}' was not found in any config file"));
+        }
+    }
+    for profile in profiles {
+        merged.merge(profile);
+    }
+    merged.apply(args, matches)
+}
+
+fn set_outside_config(matches: &ArgMatches, id: &str) -> bool {
+    matches!(
+        matches.value_source(id),
+        Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+    )
+}
+
+/// Flags turned on in a config file are turned off for one run with their negation
+fn negation(negation: &'static str, flag: &'static str) -> Arg {
+    Arg::new(negation)
+        .long(negation)
+        .action(ArgAction::SetTrue)
+        .overrides_with(flag)
+        .hide(true)
+}
+
+macro_rules! config_args {
+    (
+        flags { $($flag:ident: $negation:literal),* $(,)? }
+        values { $($value:ident: $value_type:ty),* $(,)? }
+        optional { $($optional:ident: $optional_type:ty),* $(,)? }
+        dates { $($date:ident: $parser:path),* $(,)? }
+        watch { $($watch:ident: $watch_type:ty),* $(,)? }
+        import { $($import:ident: $import_negation:literal),* $(,)? }
+    ) => {
+        /// Every option of [`Args`] and its subcommands, unset when the file leaves it out
+        #[derive(Debug, Default, Deserialize)]
+        #[serde(rename_all = "kebab-case", deny_unknown_fields)]
+        pub struct ConfigArgs {
+            $($flag: Option<bool>,)*
+            $($value: Option<$value_type>,)*
+            $($optional: Option<$optional_type>,)*
+            $($date: Option<String>,)*
+            $($watch: Option<$watch_type>,)*
+            $($import: Option<bool>,)*
+        }
+
+        /// Add `--no-front` and the like, which win over config files
+        pub fn add_negations(command: clap::Command) -> clap::Command {
+            command
+                $(.arg(negation($negation, stringify!($flag))))*
+                .mut_subcommand("import", |import| {
+                    import $(.arg(negation($import_negation, stringify!($import))))*
+                })
+        }
+
+        impl ConfigArgs {
+            fn merge(&mut self, other: ConfigArgs) {
+                $(if other.$flag.is_some() { self.$flag = other.$flag; })*
+                $(if other.$value.is_some() { self.$value = other.$value; })*
+                $(if other.$optional.is_some() { self.$optional = other.$optional; })*
+                $(if other.$date.is_some() { self.$date = other.$date; })*
+                $(if other.$watch.is_some() { self.$watch = other.$watch; })*
+                $(if other.$import.is_some() { self.$import = other.$import; })*
+            }
+
+            fn apply(self, args: &mut Args, matches: &ArgMatches) -> Result<()> {
+                $(if matches.get_flag($negation) {
+                    args.$flag = false;
+                } else if let Some(value) = self.$flag {
+                    if !set_outside_config(matches, stringify!($flag)) {
+                        args.$flag = value;
+                    }
+                })*
+                $(if let Some(value) = self.$value {
+                    if !set_outside_config(matches, stringify!($value)) {
+                        args.$value = value;
+                    }
+                })*
+                $(if let Some(value) = self.$optional {
+                    if !set_outside_config(matches, stringify!($optional)) {
+                        args.$optional = Some(value);
+                    }
+                })*
+                $(if let Some(value) = self.$date {
+                    if !set_outside_config(matches, stringify!($date)) {
+                        args.$date = Some($parser(&value).map_err(|err| {
+                            anyhow!("invalid {} in config: {err}", stringify!($date))
+                        })?);
+                    }
+                })*
+                match (&mut args.command, matches.subcommand()) {
+                    (Some(Command::Watch(watch)), Some((_, matches))) => {
+                        $(if let Some(value) = self.$watch {
+                            if !set_outside_config(matches, stringify!($watch)) {
+                                watch.$watch = value;
+                            }
+                        })*
+                    }
+                    (Some(Command::Import(import)), Some((_, matches))) => {
+                        $(if matches.get_flag($import_negation) {
+                            import.$import = false;
+                        } else if let Some(value) = self.$import {
+                            if !set_outside_config(matches, stringify!($import)) {
+                                import.$import = value;
+                            }
+                        })*
+                    }
+                    _ => {}
+                }
+                Ok(())
+            }
+        }
+    };
+}
+
+config_args! {
+    flags {
+        extension: "no-extension",
+        regex: "no-regex",
+        fix_extension: "no-fix-extension",
+        no_sidecars: "sidecars",
+        front: "no-front",
+        no_name: "keep-name",
+        twelve: "no-twelve",
+        date: "no-date",
+        space: "no-space",
+        suffix: "no-suffix",
+        preview: "no-preview",
+        images: "no-images",
+        videos: "no-videos",
+        audio: "no-audio",
+        documents: "no-documents",
+        archives: "no-archives",
+        all: "no-all",
+    }
+    values {
+        ext: Vec<String>,
+        exclude_ext: Vec<String>,
+        include: Vec<String>,
+        exclude: Vec<String>,
+    }
+    optional {
+        name: String,
+        format: String,
+        folders: String,
+        missing: String,
+        places: String,
+        source: String,
+        target: String,
+        jobs: usize,
+        metadata_jobs: usize,
+        copy_jobs: usize,
+    }
+    dates {
+        since: parse_since,
+        until: parse_until,
+    }
+    watch {
+        settle: u64,
+        debounce: u64,
+    }
+    import {
+        hash: "no-hash",
+        clear: "no-clear",
+        eject: "no-eject",
+    }
+}
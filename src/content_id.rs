@@ -0,0 +1,86 @@
+use std::{fs::File, io::Read, path::Path};
+
+use crate::quicktime::{find_atom, metadata_value, read_moov};
+
+/// Bytes searched for the Apple maker note, it sits in the EXIF data near the start
+const MAKER_NOTE_SEARCH_LENGTH: u64 = 1024 * 1024;
+
+const MAKER_NOTE_SIGNATURE: &[u8] = b"Apple iOS\0";
+const MAKER_NOTE_CONTENT_IDENTIFIER: u16 = 0x0011;
+const QUICKTIME_CONTENT_IDENTIFIER: &[u8] = b"com.apple.quicktime.content.identifier";
+
+/// The identifier iPhones write into both halves of a Live Photo
+///
+/// Images keep it in the Apple maker note, videos in the QuickTime metadata.
+pub(crate) fn content_identifier(path: &Path) -> Option<String> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    let mut file = File::open(path).ok()?;
+    match extension.as_str() {
+        "heic" | "heif" | "hif" | "jpg" | "jpeg" => maker_note_identifier(&mut file),
+        "mov" | "qt" | "mp4" => quicktime_identifier(&mut file),
+        _ => None,
+    }
+}
+
+fn maker_note_identifier(file: &mut File) -> Option<String> {
+    let mut data = vec![];
+    file.take(MAKER_NOTE_SEARCH_LENGTH)
+        .read_to_end(&mut data)
+        .ok()?;
+    let start = data
+        .windows(MAKER_NOTE_SIGNATURE.len())
+        .position(|window| window == MAKER_NOTE_SIGNATURE)?;
+    let note = &data[start..];
+    // Signature, version and byte order, offsets are relative to the signature
+    let little_endian = match note.get(12..14)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let u16_at = |offset: usize| {
+        let bytes: [u8; 2] = note.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    };
+    let u32_at = |offset: usize| {
+        let bytes: [u8; 4] = note.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    };
+    let entries = u16_at(14)?;
+    for entry in 0..usize::from(entries) {
+        let entry = 16 + entry * 12;
+        if u16_at(entry)? != MAKER_NOTE_CONTENT_IDENTIFIER {
+            continue;
+        }
+        let length = u32_at(entry + 4)? as usize;
+        let value = if length <= 4 {
+            note.get(entry + 8..entry + 8 + length)?
+        } else {
+            let offset = u32_at(entry + 8)? as usize;
+            note.get(offset..offset + length)?
+        };
+        return text(value);
+    }
+    None
+}
+
+fn quicktime_identifier(file: &mut File) -> Option<String> {
+    let moov = read_moov(file)?;
+    let meta = find_atom(&moov, b"meta")?;
+    metadata_value(meta, QUICKTIME_CONTENT_IDENTIFIER).and_then(text)
+}
+
+fn text(value: &[u8]) -> Option<String> {
+    let text = std::str::from_utf8(value)
+        .ok()?
+        .trim_end_matches('\0')
+        .trim();
+    (!text.is_empty()).then(|| text.to_owned())
+}
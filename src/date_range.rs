@@ -0,0 +1,83 @@
+use chrono::{
+    DateTime, Duration, Local, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeZone,
+};
+
+const DATE_TIME_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+];
+
+/// Files with a resolved date outside of `--since` and `--until` are filtered out
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DateRange {
+    pub since: Option<DateTime<Local>>,
+    pub until: Option<DateTime<Local>>,
+}
+
+impl DateRange {
+    pub fn contains(&self, date: &DateTime<Local>) -> bool {
+        self.since.is_none_or(|since| *date >= since)
+            && self.until.is_none_or(|until| *date <= until)
+    }
+}
+
+/// Parses `--since`, a date is taken as the start of that day
+pub fn parse_since(value: &str) -> Result<DateTime<Local>, String> {
+    parse_date_bound(value, NaiveTime::MIN)
+}
+
+/// Parses `--until`, a date is taken as the end of that day
+pub fn parse_until(value: &str) -> Result<DateTime<Local>, String> {
+    parse_date_bound(
+        value,
+        NaiveTime::from_hms_nano_opt(23, 59, 59, 999_999_999).unwrap_or(NaiveTime::MIN),
+    )
+}
+
+fn parse_date_bound(value: &str, time_of_day: NaiveTime) -> Result<DateTime<Local>, String> {
+    let value = value.trim();
+    // Values too far back for a date are rejected below
+    if let Some(date_time) =
+        parse_relative(value).and_then(|duration| Local::now().checked_sub_signed(duration))
+    {
+        return Ok(date_time);
+    }
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date_time.with_timezone(&Local));
+    }
+    for format in DATE_TIME_FORMATS {
+        if let Ok(date_time) = NaiveDateTime::parse_from_str(value, format) {
+            return to_local(date_time, value);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return to_local(date.and_time(time_of_day), value);
+    }
+    Err(format!(
+        "'{value}' is not a date ('2024-07-17'), datetime ('2024-07-17 14:30') or relative value ('30d')"
+    ))
+}
+
+/// Relative values count back from now, e.g. `90m`, `12h`, `30d` or `2w`
+fn parse_relative(value: &str) -> Option<Duration> {
+    let unit = value.chars().last()?;
+    let amount: i64 = value[..value.len() - unit.len_utf8()].parse().ok()?;
+    match unit {
+        's' => Duration::try_seconds(amount),
+        'm' => Duration::try_minutes(amount),
+        'h' => Duration::try_hours(amount),
+        'd' => Duration::try_days(amount),
+        'w' => Duration::try_weeks(amount),
+        _ => None,
+    }
+}
+
+fn to_local(date_time: NaiveDateTime, value: &str) -> Result<DateTime<Local>, String> {
+    match Local.from_local_datetime(&date_time) {
+        LocalResult::Single(date_time) => Ok(date_time),
+        LocalResult::Ambiguous(earliest, _) => Ok(earliest),
+        LocalResult::None => Err(format!("'{value}' does not exist in the local timezone")),
+    }
+}
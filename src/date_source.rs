@@ -0,0 +1,151 @@
+use std::{fmt, fs::Metadata, path::Path, sync::Arc};
+
+use chrono::{DateTime, FixedOffset, Local};
+
+use crate::{
+    audio_metadata::AudioMetadata, document_metadata::DocumentMetadata,
+    image_metadata::ImageMetadata, takeout::GoogleTakeout, video_metadata::VideoMetadata, xmp::Xmp,
+};
+
+/// How much a date from a [`DateSource`] can be trusted, higher wins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    /// Filesystem dates that change when files are copied or edited
+    Low,
+    /// Dates written by software that may not be the time of capture
+    Medium,
+    /// Dates recorded by the device when the file was created
+    High,
+}
+
+/// A date found by a [`DateSource`]
+///
+/// The offset is kept so names use the wall clock time the file was created in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FoundDate {
+    pub date: DateTime<FixedOffset>,
+    pub confidence: Confidence,
+}
+
+impl FoundDate {
+    pub fn new(date: DateTime<FixedOffset>, confidence: Confidence) -> Self {
+        FoundDate { date, confidence }
+    }
+}
+
+/// Finds the date a file was created, e.g. from metadata in the file contents
+///
+/// Sources are called from a blocking thread, so they can read the file themselves
+/// when `header` (the first [`HEADER_LENGTH`](crate::HEADER_LENGTH) bytes) is not enough.
+///
+/// ```
+/// use std::{fs::Metadata, path::Path};
+///
+/// use chrono::DateTime;
+/// use createdat::{Confidence, DateSource, FoundDate};
+///
+/// /// Instrument output starting with `TIMESTAMP=2024-07-17T14:30:00+02:00`
+/// struct InstrumentHeader;
+///
+/// impl DateSource for InstrumentHeader {
+///     fn name(&self) -> &str {
+///         "instrument header"
+///     }
+///
+///     fn find_date(&self, _path: &Path, _metadata: &Metadata, header: &[u8]) -> Option<FoundDate> {
+///         let line = std::str::from_utf8(header.strip_prefix(b"TIMESTAMP=")?.get(..25)?).ok()?;
+///         let date = DateTime::parse_from_rfc3339(line).ok()?;
+///         Some(FoundDate::new(date, Confidence::High))
+///     }
+/// }
+/// ```
+pub trait DateSource: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn find_date(&self, path: &Path, metadata: &Metadata, header: &[u8]) -> Option<FoundDate>;
+}
+
+/// The date sources consulted for every file
+///
+/// The date with the highest [`Confidence`] is used, earlier sources win ties.
+/// Sources added with [`DateSources::register`] come before the built-in ones.
+#[derive(Clone)]
+pub struct DateSources {
+    sources: Vec<Arc<dyn DateSource>>,
+    registered: usize,
+}
+
+impl DateSources {
+    /// Only the built-in sources
+    pub fn builtin() -> Self {
+        DateSources {
+            sources: vec![
+                Arc::new(GoogleTakeout),
+                Arc::new(ImageMetadata),
+                Arc::new(AudioMetadata),
+                Arc::new(VideoMetadata),
+                Arc::new(DocumentMetadata),
+                Arc::new(Xmp),
+                Arc::new(FileModified),
+            ],
+            registered: 0,
+        }
+    }
+
+    /// No sources at all, not even the file modification time
+    pub fn empty() -> Self {
+        DateSources {
+            sources: vec![],
+            registered: 0,
+        }
+    }
+
+    pub fn register(&mut self, source: impl DateSource + 'static) {
+        self.sources.insert(self.registered, Arc::new(source));
+        self.registered += 1;
+    }
+
+    pub fn find_date(&self, path: &Path, metadata: &Metadata, header: &[u8]) -> Option<FoundDate> {
+        let mut best: Option<FoundDate> = None;
+        for source in &self.sources {
+            let Some(found) = source.find_date(path, metadata, header) else {
+                continue;
+            };
+            if best.is_none_or(|best| found.confidence > best.confidence) {
+                best = Some(found);
+            }
+            if found.confidence == Confidence::High {
+                break;
+            }
+        }
+        best
+    }
+}
+
+impl Default for DateSources {
+    fn default() -> Self {
+        DateSources::builtin()
+    }
+}
+
+impl fmt::Debug for DateSources {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.sources.iter().map(|source| source.name()))
+            .finish()
+    }
+}
+
+/// The last time the file was modified, which is kept when files are copied from cameras
+pub struct FileModified;
+
+impl DateSource for FileModified {
+    fn name(&self) -> &str {
+        "file modified"
+    }
+
+    fn find_date(&self, _path: &Path, metadata: &Metadata, _header: &[u8]) -> Option<FoundDate> {
+        let modified: DateTime<Local> = metadata.modified().ok()?.into();
+        Some(FoundDate::new(modified.fixed_offset(), Confidence::Low))
+    }
+}
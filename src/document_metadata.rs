@@ -0,0 +1,189 @@
+use std::{
+    fs::{File, Metadata},
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone};
+
+use crate::{
+    date_source::{Confidence, DateSource, FoundDate},
+    file_type::{sniff, FileType},
+    xmp,
+};
+
+/// Bytes searched at the start and the end of a PDF, where the document info usually is
+const PDF_SEARCH_LENGTH: u64 = 1024 * 1024;
+
+/// Largest metadata file that is read from an office document
+const MAX_METADATA_LENGTH: u64 = 4 * 1024 * 1024;
+
+const DCTERMS_NAMESPACE: &str = "http://purl.org/dc/terms/";
+const ODF_META_NAMESPACE: &str = "urn:oasis:names:tc:opendocument:xmlns:meta:1.0";
+
+/// The creation date stored in PDF, Office Open XML and OpenDocument files
+///
+/// PDFs have a `/CreationDate` in their document info, XMP in PDFs is read by
+/// [`Xmp`](crate::Xmp). Word, Excel and PowerPoint files have `dcterms:created` in
+/// `docProps/core.xml`, OpenDocument files `meta:creation-date` in `meta.xml`.
+pub struct DocumentMetadata;
+
+impl DateSource for DocumentMetadata {
+    fn name(&self) -> &str {
+        "document metadata"
+    }
+
+    fn find_date(&self, path: &Path, _metadata: &Metadata, header: &[u8]) -> Option<FoundDate> {
+        let date = match sniff(header)? {
+            FileType::Pdf => pdf_date(path)?,
+            FileType::Docx | FileType::Xlsx | FileType::Pptx => {
+                zip_xml_date(path, "docProps/core.xml", (DCTERMS_NAMESPACE, "created"))?
+            }
+            FileType::Odt | FileType::Ods | FileType::Odp => {
+                zip_xml_date(path, "meta.xml", (ODF_META_NAMESPACE, "creation-date"))?
+            }
+            _ => return None,
+        };
+        // Written by the software that made the document, which may have used a template
+        Some(FoundDate::new(date, Confidence::Medium))
+    }
+}
+
+fn pdf_date(path: &Path) -> Option<DateTime<FixedOffset>> {
+    let mut file = File::open(path).ok()?;
+    let length = file.metadata().ok()?.len();
+    let mut head = vec![];
+    (&mut file)
+        .take(PDF_SEARCH_LENGTH)
+        .read_to_end(&mut head)
+        .ok()?;
+    if let Some(date) = find_creation_date(&head) {
+        return Some(date);
+    }
+    if length <= PDF_SEARCH_LENGTH {
+        return None;
+    }
+    let mut tail = vec![];
+    file.seek(SeekFrom::Start(
+        length
+            .saturating_sub(PDF_SEARCH_LENGTH)
+            .max(PDF_SEARCH_LENGTH),
+    ))
+    .ok()?;
+    file.read_to_end(&mut tail).ok()?;
+    find_creation_date(&tail)
+}
+
+/// `/CreationDate (D:20240717143000+02'00')` in the document info dictionary, or the same as
+/// a hex string `<443A32...>`
+///
+/// Values that aren't strings, like a reference to another object, are passed over for the
+/// dictionaries of later revisions.
+fn find_creation_date(data: &[u8]) -> Option<DateTime<FixedOffset>> {
+    const KEY: &[u8] = b"/CreationDate";
+    let mut rest = data;
+    while let Some(position) = rest.windows(KEY.len()).position(|window| window == KEY) {
+        rest = &rest[position + KEY.len()..];
+        let value = rest.trim_ascii_start();
+        let text = if let Some(value) = value.strip_prefix(b"(") {
+            let Some(end) = value.iter().position(|&byte| byte == b')') else {
+                continue;
+            };
+            String::from_utf8_lossy(&value[..end]).into_owned()
+        } else if let Some(value) = value.strip_prefix(b"<") {
+            let Some(end) = value.iter().position(|&byte| byte == b'>') else {
+                continue;
+            };
+            let Some(text) = decode_hex_string(&value[..end]) else {
+                continue;
+            };
+            text
+        } else {
+            continue;
+        };
+        if let Some(date) = parse_pdf_date(&text) {
+            return Some(date);
+        }
+    }
+    None
+}
+
+/// Pairs of hex digits, a missing last digit is zero. Strings with a byte order mark are UTF-16
+fn decode_hex_string(hex: &[u8]) -> Option<String> {
+    let mut digits = hex
+        .iter()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .map(|&byte| char::from(byte).to_digit(16))
+        .collect::<Option<Vec<_>>>()?;
+    if digits.len() % 2 == 1 {
+        digits.push(0);
+    }
+    let bytes: Vec<u8> = digits
+        .chunks(2)
+        .map(|pair| (pair[0] * 16 + pair[1]) as u8)
+        .collect();
+    match bytes.strip_prefix(b"\xFE\xFF") {
+        Some(utf16) => String::from_utf16(
+            &utf16
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect::<Vec<_>>(),
+        )
+        .ok(),
+        None => String::from_utf8(bytes).ok(),
+    }
+}
+
+/// `D:YYYYMMDDHHmmSSOHH'mm'`, everything after the year is optional and without a time
+/// zone the date is in local time
+fn parse_pdf_date(value: &str) -> Option<DateTime<FixedOffset>> {
+    let value = value.trim().strip_prefix("D:").unwrap_or(value.trim());
+    let digits = value
+        .find(|character: char| !character.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (date, zone) = value.split_at(digits);
+    // Only a full date is useful for a name
+    if date.len() < 8 {
+        return None;
+    }
+    let padded = format!("{date:0<14}");
+    let date = NaiveDateTime::parse_from_str(&padded, "%Y%m%d%H%M%S").ok()?;
+
+    let zone = zone.replace('\'', "");
+    let offset = match zone.chars().next() {
+        Some('Z') => Some(FixedOffset::east_opt(0)?),
+        Some(sign @ ('+' | '-')) => {
+            let hours: i32 = zone.get(1..3)?.parse().ok()?;
+            let minutes: i32 = zone.get(3..5).and_then(|m| m.parse().ok()).unwrap_or(0);
+            let seconds = (hours * 60 + minutes) * 60;
+            FixedOffset::east_opt(if sign == '-' { -seconds } else { seconds })
+        }
+        _ => None,
+    };
+    match offset {
+        Some(offset) => offset.from_local_datetime(&date).single(),
+        None => Some(Local.from_local_datetime(&date).earliest()?.fixed_offset()),
+    }
+}
+
+/// The date in an element of an XML file inside a zip based document
+fn zip_xml_date(
+    path: &Path,
+    entry: &str,
+    (namespace, name): (&str, &str),
+) -> Option<DateTime<FixedOffset>> {
+    let mut archive = zip::ZipArchive::new(File::open(path).ok()?).ok()?;
+    let mut xml = String::new();
+    archive
+        .by_name(entry)
+        .ok()?
+        .take(MAX_METADATA_LENGTH)
+        .read_to_string(&mut xml)
+        .ok()?;
+    let document = roxmltree::Document::parse(&xml).ok()?;
+    let value = document
+        .descendants()
+        .find(|node| node.has_tag_name((namespace, name)))?
+        .text()?;
+    xmp::parse_date(value)
+}
@@ -0,0 +1,76 @@
+use std::{io, path::PathBuf};
+
+/// Errors from renaming files, see [`SkipReason`] for files that were not selected
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The file name is not valid UTF-8
+    #[error("file name of {path:?} is not valid UTF-8")]
+    UnreadableName { path: PathBuf },
+
+    /// None of the date sources found a date for the file
+    #[error("reading the date of {path:?} is not supported")]
+    UnsupportedMetadata { path: PathBuf },
+
+    /// Another file already exists at the destination
+    #[error("{destination:?} already exists")]
+    Conflict { path: PathBuf, destination: PathBuf },
+
+    /// An include or exclude pattern could not be parsed
+    #[error("invalid pattern: {0}")]
+    Pattern(String),
+
+    /// A custom date or folder format has a `%` specifier that isn't supported
+    #[error("invalid date format {0:?}")]
+    Format(String),
+
+    /// The places file for `{city}` and `{country}` has no cities in the GeoNames format
+    #[error("no cities found in {path:?}, expected a GeoNames file")]
+    Places { path: PathBuf },
+
+    #[error("{path:?}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+}
+
+impl Error {
+    pub(crate) fn io(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> Error {
+        let path = path.into();
+        move |source| Error::Io { path, source }
+    }
+}
+
+impl From<globset::Error> for Error {
+    fn from(err: globset::Error) -> Self {
+        Error::Pattern(err.to_string())
+    }
+}
+
+impl From<regex::Error> for Error {
+    fn from(err: regex::Error) -> Self {
+        Error::Pattern(err.to_string())
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Why a file was left out of a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    Directory,
+    /// Not picked with `--extension`, not in `--ext` or listed in `--exclude-ext`
+    Extension,
+    /// Not matched by `--include` or matched by `--exclude`
+    Pattern,
+    /// Not one of the selected categories
+    Category,
+    /// Dated outside of `--since` and `--until`
+    DateRange,
+    /// A sidecar whose primary file was not renamed
+    Companion,
+}
@@ -0,0 +1,243 @@
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone};
+
+use crate::date_source::{Confidence, FoundDate};
+
+const MAKE: u16 = 0x010F;
+const MODEL: u16 = 0x0110;
+const DATE_TIME: u16 = 0x0132;
+const XML_PACKET: u16 = 0x02BC;
+const EXIF_IFD: u16 = 0x8769;
+const GPS_IFD: u16 = 0x8825;
+const GPS_LATITUDE_REF: u16 = 0x0001;
+const GPS_LATITUDE: u16 = 0x0002;
+const GPS_LONGITUDE_REF: u16 = 0x0003;
+const GPS_LONGITUDE: u16 = 0x0004;
+const DATE_TIME_ORIGINAL: u16 = 0x9003;
+const DATE_TIME_DIGITIZED: u16 = 0x9004;
+const OFFSET_TIME: u16 = 0x9010;
+const OFFSET_TIME_ORIGINAL: u16 = 0x9011;
+const OFFSET_TIME_DIGITIZED: u16 = 0x9012;
+const ISO: u16 = 0x8827;
+const PIXEL_X_DIMENSION: u16 = 0xA002;
+const PIXEL_Y_DIMENSION: u16 = 0xA003;
+const LENS_MODEL: u16 = 0xA434;
+
+/// Types of ASCII, unsigned short, unsigned long and unsigned rational values in an IFD entry
+const ASCII: u16 = 2;
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const RATIONAL: u16 = 5;
+
+/// Fields read from EXIF data, the TIFF structure JPEG, PNG, WebP and raw files share
+#[derive(Debug, Default)]
+pub(crate) struct Exif {
+    pub(crate) date_time_original: Option<DateTime<FixedOffset>>,
+    pub(crate) date_time_digitized: Option<DateTime<FixedOffset>>,
+    pub(crate) date_time: Option<DateTime<FixedOffset>>,
+    pub(crate) make: Option<String>,
+    pub(crate) model: Option<String>,
+    pub(crate) lens: Option<String>,
+    pub(crate) iso: Option<u32>,
+    pub(crate) width: Option<u32>,
+    pub(crate) height: Option<u32>,
+    /// Latitude and longitude in degrees, south and west are negative
+    pub(crate) gps: Option<(f64, f64)>,
+}
+
+impl Exif {
+    /// Parse EXIF data starting with the TIFF header, `Exif\0\0` in front of it is skipped
+    pub(crate) fn parse(data: &[u8]) -> Option<Exif> {
+        let tiff = Tiff::new(data.strip_prefix(b"Exif\0\0").unwrap_or(data))?;
+        let ifd0 = tiff.ifd(tiff.u32(4)? as usize)?;
+        let exif_ifd = ifd0
+            .iter()
+            .find(|entry| entry.tag == EXIF_IFD)
+            .and_then(|entry| tiff.ifd(tiff.entry_u32(entry)? as usize))
+            .unwrap_or_default();
+        let gps_ifd = ifd0
+            .iter()
+            .find(|entry| entry.tag == GPS_IFD)
+            .and_then(|entry| tiff.ifd(tiff.entry_u32(entry)? as usize))
+            .unwrap_or_default();
+
+        let text = |entries: &[Entry], tag: u16| {
+            let entry = entries.iter().find(|entry| entry.tag == tag)?;
+            tiff.ascii(entry)
+        };
+        let date = |entries: &[Entry], tag: u16, offset_tag: u16| {
+            let offset = text(&exif_ifd, offset_tag);
+            parse_date(text(entries, tag)?, offset)
+        };
+        let string = |entries: &[Entry], tag: u16| Some(text(entries, tag)?.trim().to_owned());
+        let number = |tag: u16| {
+            let entry = exif_ifd.iter().find(|entry| entry.tag == tag)?;
+            tiff.number(entry)
+        };
+        // Degrees, minutes and seconds, with `N`, `S`, `E` or `W` in a separate tag
+        let coordinate = |reference_tag: u16, tag: u16, negative: &str| {
+            let entry = gps_ifd.iter().find(|entry| entry.tag == tag)?;
+            let [degrees, minutes, seconds] = tiff.rationals(entry)?[..] else {
+                return None;
+            };
+            let value = degrees + minutes / 60.0 + seconds / 3600.0;
+            let reference = text(&gps_ifd, reference_tag).unwrap_or_default();
+            Some(if reference.starts_with(negative) {
+                -value
+            } else {
+                value
+            })
+        };
+        let gps = coordinate(GPS_LATITUDE_REF, GPS_LATITUDE, "S")
+            .zip(coordinate(GPS_LONGITUDE_REF, GPS_LONGITUDE, "W"))
+            .filter(|&(latitude, longitude)| latitude != 0.0 || longitude != 0.0);
+        Some(Exif {
+            date_time_original: date(&exif_ifd, DATE_TIME_ORIGINAL, OFFSET_TIME_ORIGINAL),
+            date_time_digitized: date(&exif_ifd, DATE_TIME_DIGITIZED, OFFSET_TIME_DIGITIZED),
+            date_time: date(&ifd0, DATE_TIME, OFFSET_TIME),
+            make: string(&ifd0, MAKE),
+            model: string(&ifd0, MODEL),
+            lens: string(&exif_ifd, LENS_MODEL),
+            iso: number(ISO),
+            width: number(PIXEL_X_DIMENSION),
+            height: number(PIXEL_Y_DIMENSION),
+            gps,
+        })
+    }
+
+    /// When the photo was taken, or when the file was last changed by software
+    pub(crate) fn date(&self) -> Option<FoundDate> {
+        self.date_time_original
+            .or(self.date_time_digitized)
+            .map(|date| FoundDate::new(date, Confidence::High))
+            .or_else(|| {
+                self.date_time
+                    .map(|date| FoundDate::new(date, Confidence::Medium))
+            })
+    }
+}
+
+/// Offset and length of the XMP packet that TIFF and raw files keep in IFD0
+pub(crate) fn xmp_location(data: &[u8]) -> Option<(u64, u64)> {
+    let tiff = Tiff::new(data)?;
+    let ifd0 = tiff.ifd(tiff.u32(4)? as usize)?;
+    let entry = ifd0.iter().find(|entry| entry.tag == XML_PACKET)?;
+    // Byte values longer than four bytes are stored at an offset
+    if entry.count <= 4 {
+        return None;
+    }
+    Some((tiff.entry_u32(entry)?.into(), entry.count.into()))
+}
+
+/// `2024:07:17 14:30:00`, in the time zone of `offset` (`+02:00`) or else in local time
+pub(crate) fn parse_date(value: &str, offset: Option<&str>) -> Option<DateTime<FixedOffset>> {
+    let naive = NaiveDateTime::parse_from_str(value.trim(), "%Y:%m:%d %H:%M:%S").ok()?;
+    match offset.and_then(|offset| offset.trim().parse::<FixedOffset>().ok()) {
+        Some(offset) => offset.from_local_datetime(&naive).single(),
+        None => Some(Local.from_local_datetime(&naive).earliest()?.fixed_offset()),
+    }
+}
+
+/// An IFD entry, `value` is where its value or the offset to it is stored
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    tag: u16,
+    kind: u16,
+    count: u32,
+    value: usize,
+}
+
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    /// Raw formats like ORF and RW2 use their own magic number after the byte order
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(..2)? {
+            b"II" => true,
+            b"MM" => false,
+            _ => return None,
+        };
+        Some(Tiff {
+            data,
+            little_endian,
+        })
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = self.data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn ifd(&self, offset: usize) -> Option<Vec<Entry>> {
+        let count = self.u16(offset)?;
+        (0..usize::from(count))
+            .map(|index| {
+                let entry = offset + 2 + index * 12;
+                Some(Entry {
+                    tag: self.u16(entry)?,
+                    kind: self.u16(entry + 2)?,
+                    count: self.u32(entry + 4)?,
+                    value: entry + 8,
+                })
+            })
+            .collect()
+    }
+
+    fn entry_u32(&self, entry: &Entry) -> Option<u32> {
+        self.u32(entry.value)
+    }
+
+    /// The first value of a short or long entry
+    fn number(&self, entry: &Entry) -> Option<u32> {
+        match entry.kind {
+            SHORT => self.u16(entry.value).map(u32::from),
+            LONG => self.u32(entry.value),
+            _ => None,
+        }
+    }
+
+    /// Rational values, always stored at an offset since each takes eight bytes
+    fn rationals(&self, entry: &Entry) -> Option<Vec<f64>> {
+        if entry.kind != RATIONAL {
+            return None;
+        }
+        let start = self.u32(entry.value)? as usize;
+        (0..entry.count as usize)
+            .map(|index| {
+                let numerator = self.u32(start + index * 8)?;
+                let denominator = self.u32(start + index * 8 + 4)?;
+                (denominator != 0).then(|| f64::from(numerator) / f64::from(denominator))
+            })
+            .collect()
+    }
+
+    fn ascii(&self, entry: &Entry) -> Option<&'a str> {
+        if entry.kind != ASCII {
+            return None;
+        }
+        let length = entry.count as usize;
+        let start = if length <= 4 {
+            entry.value
+        } else {
+            self.u32(entry.value)? as usize
+        };
+        let bytes = self.data.get(start..start.checked_add(length)?)?;
+        let text = std::str::from_utf8(bytes).ok()?.trim_end_matches('\0');
+        (!text.trim().is_empty()).then_some(text)
+    }
+}
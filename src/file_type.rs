@@ -0,0 +1,365 @@
+use std::path::Path;
+
+use tokio::{fs::File, io::AsyncReadExt};
+
+/// Number of bytes read from the start of a file to detect its type and date
+pub const HEADER_LENGTH: usize = 64 * 1024;
+
+/// File types recognized by their magic numbers instead of their extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    Jpeg,
+    Png,
+    Gif,
+    Webp,
+    Bmp,
+    Tiff,
+    Heic,
+    Heif,
+    Avif,
+    Cr3,
+    Orf,
+    Rw2,
+    Raf,
+    Mp4,
+    Mov,
+    M4v,
+    ThreeGp,
+    Avi,
+    Mkv,
+    Webm,
+    Mp3,
+    M4a,
+    Flac,
+    Ogg,
+    Wav,
+    Aiff,
+    Pdf,
+    Rtf,
+    Docx,
+    Xlsx,
+    Pptx,
+    Odt,
+    Ods,
+    Odp,
+    Epub,
+    Zip,
+    Tar,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+    SevenZip,
+    Rar,
+}
+
+/// Groups of file types that can be selected together
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Image,
+    Video,
+    Audio,
+    Document,
+    Archive,
+}
+
+impl Category {
+    pub fn from_mime(mime: &str) -> Option<Category> {
+        let (kind, subtype) = mime.split_once('/')?;
+        match kind {
+            "image" => Some(Category::Image),
+            "video" => Some(Category::Video),
+            "audio" => Some(Category::Audio),
+            "text" if matches!(subtype, "plain" | "markdown" | "csv" | "rtf") => {
+                Some(Category::Document)
+            }
+            "application" => match subtype {
+                "pdf" | "rtf" | "msword" | "epub+zip" | "vnd.ms-excel" | "vnd.ms-powerpoint" => {
+                    Some(Category::Document)
+                }
+                _ if subtype.starts_with("vnd.openxmlformats-officedocument.")
+                    || subtype.starts_with("vnd.oasis.opendocument.") =>
+                {
+                    Some(Category::Document)
+                }
+                "zip" | "x-tar" | "gzip" | "x-gzip" | "x-bzip2" | "x-xz" | "zstd"
+                | "x-7z-compressed" | "vnd.rar" | "x-rar-compressed" => Some(Category::Archive),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl FileType {
+    pub fn mime(&self) -> &'static str {
+        match self {
+            FileType::Jpeg => "image/jpeg",
+            FileType::Png => "image/png",
+            FileType::Gif => "image/gif",
+            FileType::Webp => "image/webp",
+            FileType::Bmp => "image/bmp",
+            FileType::Tiff => "image/tiff",
+            FileType::Heic => "image/heic",
+            FileType::Heif => "image/heif",
+            FileType::Avif => "image/avif",
+            FileType::Cr3 => "image/x-canon-cr3",
+            FileType::Orf => "image/x-olympus-orf",
+            FileType::Rw2 => "image/x-panasonic-rw2",
+            FileType::Raf => "image/x-fuji-raf",
+            FileType::Mp4 => "video/mp4",
+            FileType::Mov => "video/quicktime",
+            FileType::M4v => "video/x-m4v",
+            FileType::ThreeGp => "video/3gpp",
+            FileType::Avi => "video/x-msvideo",
+            FileType::Mkv => "video/x-matroska",
+            FileType::Webm => "video/webm",
+            FileType::Mp3 => "audio/mpeg",
+            FileType::M4a => "audio/mp4",
+            FileType::Flac => "audio/flac",
+            FileType::Ogg => "audio/ogg",
+            FileType::Wav => "audio/wav",
+            FileType::Aiff => "audio/aiff",
+            FileType::Pdf => "application/pdf",
+            FileType::Rtf => "application/rtf",
+            FileType::Docx => {
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+            }
+            FileType::Xlsx => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            FileType::Pptx => {
+                "application/vnd.openxmlformats-officedocument.presentationml.presentation"
+            }
+            FileType::Odt => "application/vnd.oasis.opendocument.text",
+            FileType::Ods => "application/vnd.oasis.opendocument.spreadsheet",
+            FileType::Odp => "application/vnd.oasis.opendocument.presentation",
+            FileType::Epub => "application/epub+zip",
+            FileType::Zip => "application/zip",
+            FileType::Tar => "application/x-tar",
+            FileType::Gzip => "application/gzip",
+            FileType::Bzip2 => "application/x-bzip2",
+            FileType::Xz => "application/x-xz",
+            FileType::Zstd => "application/zstd",
+            FileType::SevenZip => "application/x-7z-compressed",
+            FileType::Rar => "application/vnd.rar",
+        }
+    }
+
+    /// Extensions used for this type, the first one is used when fixing extensions
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            FileType::Jpeg => &["jpg", "jpeg", "jpe", "jfif"],
+            FileType::Png => &["png"],
+            FileType::Gif => &["gif"],
+            FileType::Webp => &["webp"],
+            FileType::Bmp => &["bmp", "dib"],
+            // Most raw formats are TIFF based
+            FileType::Tiff => &[
+                "tif", "tiff", "dng", "cr2", "nef", "nrw", "arw", "srf", "sr2", "pef", "erf",
+                "3fr", "iiq", "mos", "rwl", "srw", "x3f",
+            ],
+            FileType::Heic => &["heic", "heif", "hif"],
+            FileType::Heif => &["heif", "heic", "hif"],
+            FileType::Avif => &["avif"],
+            FileType::Cr3 => &["cr3"],
+            FileType::Orf => &["orf"],
+            FileType::Rw2 => &["rw2"],
+            FileType::Raf => &["raf"],
+            FileType::Mp4 => &["mp4", "m4v", "m4p"],
+            FileType::Mov => &["mov", "qt"],
+            FileType::M4v => &["m4v", "mp4"],
+            FileType::ThreeGp => &["3gp", "3g2"],
+            FileType::Avi => &["avi"],
+            FileType::Mkv => &["mkv", "mka", "mk3d"],
+            FileType::Webm => &["webm"],
+            FileType::Mp3 => &["mp3"],
+            FileType::M4a => &["m4a", "m4b", "mp4"],
+            FileType::Flac => &["flac"],
+            FileType::Ogg => &["ogg", "oga", "opus", "ogv", "spx"],
+            FileType::Wav => &["wav", "wave", "bwf"],
+            FileType::Aiff => &["aiff", "aif", "aifc"],
+            FileType::Pdf => &["pdf", "ai"],
+            FileType::Rtf => &["rtf"],
+            FileType::Docx => &["docx", "docm", "dotx"],
+            FileType::Xlsx => &["xlsx", "xlsm", "xltx"],
+            FileType::Pptx => &["pptx", "pptm", "potx"],
+            FileType::Odt => &["odt"],
+            FileType::Ods => &["ods"],
+            FileType::Odp => &["odp"],
+            FileType::Epub => &["epub"],
+            // Many formats are zip files with a different extension
+            FileType::Zip => &[
+                "zip", "jar", "apk", "cbz", "xpi", "whl", "kmz", "ipa", "nupkg", "vsix",
+            ],
+            FileType::Tar => &["tar"],
+            FileType::Gzip => &["gz", "tgz"],
+            FileType::Bzip2 => &["bz2", "tbz2"],
+            FileType::Xz => &["xz", "txz"],
+            FileType::Zstd => &["zst", "tzst"],
+            FileType::SevenZip => &["7z"],
+            FileType::Rar => &["rar", "cbr"],
+        }
+    }
+
+    /// Whether `extension` is a valid extension for this type
+    pub fn has_extension(&self, extension: &str) -> bool {
+        self.extensions()
+            .iter()
+            .any(|known| known.eq_ignore_ascii_case(extension))
+    }
+}
+
+/// Reads the first [`HEADER_LENGTH`] bytes of a file
+pub async fn read_header(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut header = Vec::with_capacity(HEADER_LENGTH);
+    File::open(path)
+        .await?
+        .take(HEADER_LENGTH as u64)
+        .read_to_end(&mut header)
+        .await?;
+    Ok(header)
+}
+
+/// Detects the file type from the magic number at the start of `header`
+pub fn sniff(header: &[u8]) -> Option<FileType> {
+    let starts_with = |offset: usize, magic: &[u8]| {
+        header
+            .get(offset..offset + magic.len())
+            .is_some_and(|bytes| bytes == magic)
+    };
+
+    if starts_with(0, &[0xFF, 0xD8, 0xFF]) {
+        Some(FileType::Jpeg)
+    } else if starts_with(0, b"\x89PNG\r\n\x1a\n") {
+        Some(FileType::Png)
+    } else if starts_with(0, b"GIF87a") || starts_with(0, b"GIF89a") {
+        Some(FileType::Gif)
+    } else if starts_with(0, b"RIFF") {
+        match header.get(8..12)? {
+            b"WEBP" => Some(FileType::Webp),
+            b"AVI " => Some(FileType::Avi),
+            b"WAVE" => Some(FileType::Wav),
+            _ => None,
+        }
+    } else if starts_with(0, b"FORM") && (starts_with(8, b"AIFF") || starts_with(8, b"AIFC")) {
+        Some(FileType::Aiff)
+    } else if starts_with(0, b"IIRO") || starts_with(0, b"IIRS") || starts_with(0, b"MMOR") {
+        Some(FileType::Orf)
+    } else if starts_with(0, b"IIU\0") {
+        Some(FileType::Rw2)
+    } else if starts_with(0, b"II*\0") || starts_with(0, b"MM\0*") {
+        Some(FileType::Tiff)
+    } else if starts_with(0, b"FUJIFILMCCD-RAW") {
+        Some(FileType::Raf)
+    } else if starts_with(0, b"BM") && header.len() >= 26 && starts_with(6, &[0, 0, 0, 0]) {
+        Some(FileType::Bmp)
+    } else if starts_with(4, b"ftyp") {
+        sniff_ftyp(header)
+    } else if starts_with(4, b"moov")
+        || starts_with(4, b"mdat")
+        || starts_with(4, b"wide")
+        || starts_with(4, b"free")
+        || starts_with(4, b"pnot")
+    {
+        Some(FileType::Mov)
+    } else if starts_with(0, &[0x1A, 0x45, 0xDF, 0xA3]) {
+        let ebml_header = &header[..header.len().min(64)];
+        if ebml_header.windows(4).any(|window| window == b"webm") {
+            Some(FileType::Webm)
+        } else {
+            Some(FileType::Mkv)
+        }
+    } else if starts_with(0, b"fLaC") {
+        Some(FileType::Flac)
+    } else if starts_with(0, b"OggS") {
+        Some(FileType::Ogg)
+    } else if starts_with(0, b"%PDF-") {
+        Some(FileType::Pdf)
+    } else if starts_with(0, b"{\\rtf") {
+        Some(FileType::Rtf)
+    } else if starts_with(0, b"PK\x03\x04") {
+        Some(sniff_zip(header))
+    } else if starts_with(0, &[0x1F, 0x8B]) {
+        Some(FileType::Gzip)
+    } else if starts_with(0, b"BZh") {
+        Some(FileType::Bzip2)
+    } else if starts_with(0, &[0xFD, b'7', b'z', b'X', b'Z', 0x00]) {
+        Some(FileType::Xz)
+    } else if starts_with(0, &[0x28, 0xB5, 0x2F, 0xFD]) {
+        Some(FileType::Zstd)
+    } else if starts_with(0, &[b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C]) {
+        Some(FileType::SevenZip)
+    } else if starts_with(0, b"Rar!\x1A\x07") {
+        Some(FileType::Rar)
+    } else if starts_with(257, b"ustar") {
+        Some(FileType::Tar)
+    } else if starts_with(0, b"ID3")
+        || (header.len() >= 2 && header[0] == 0xFF && header[1] & 0xE6 == 0xE2)
+    {
+        Some(FileType::Mp3)
+    } else {
+        None
+    }
+}
+
+/// ISO base media files share the `ftyp` box, the brands tell them apart
+fn sniff_ftyp(header: &[u8]) -> Option<FileType> {
+    let box_size = u32::from_be_bytes(header.get(0..4)?.try_into().ok()?) as usize;
+    let major_brand = header.get(8..12)?;
+    let compatible_brands = header
+        .get(16..box_size.clamp(16, header.len()))
+        .unwrap_or_default();
+    let brand_type = |brand: &[u8]| match brand {
+        b"heic" | b"heix" | b"heim" | b"heis" | b"hevc" | b"hevx" => Some(FileType::Heic),
+        b"avif" | b"avis" => Some(FileType::Avif),
+        b"crx " => Some(FileType::Cr3),
+        b"qt  " => Some(FileType::Mov),
+        b"M4A " | b"M4B " | b"M4P " => Some(FileType::M4a),
+        b"M4V " | b"M4VH" | b"M4VP" => Some(FileType::M4v),
+        _ if brand.starts_with(b"3g") => Some(FileType::ThreeGp),
+        b"isom" | b"iso2" | b"iso4" | b"iso5" | b"iso6" | b"mp41" | b"mp42" | b"avc1" | b"dash"
+        | b"mmp4" | b"MSNV" | b"f4v " | b"XAVC" => Some(FileType::Mp4),
+        _ => None,
+    };
+
+    if major_brand == b"mif1" || major_brand == b"msf1" {
+        // Generic HEIF, a compatible brand says which codec is used
+        return compatible_brands
+            .chunks_exact(4)
+            .find_map(|brand| match brand_type(brand) {
+                Some(file_type @ (FileType::Heic | FileType::Avif)) => Some(file_type),
+                _ => None,
+            })
+            .or(Some(FileType::Heif));
+    }
+    brand_type(major_brand).or_else(|| {
+        compatible_brands
+            .chunks_exact(4)
+            .find_map(brand_type)
+            .or(Some(FileType::Mp4))
+    })
+}
+
+/// Office and e-book formats are zip files, the first entries tell them apart
+fn sniff_zip(header: &[u8]) -> FileType {
+    let contains = |name: &[u8]| header.windows(name.len()).any(|window| window == name);
+
+    if contains(b"mimetypeapplication/epub+zip") {
+        FileType::Epub
+    } else if contains(b"mimetypeapplication/vnd.oasis.opendocument.text") {
+        FileType::Odt
+    } else if contains(b"mimetypeapplication/vnd.oasis.opendocument.spreadsheet") {
+        FileType::Ods
+    } else if contains(b"mimetypeapplication/vnd.oasis.opendocument.presentation") {
+        FileType::Odp
+    } else if !contains(b"[Content_Types].xml") {
+        FileType::Zip
+    } else if contains(b"word/") {
+        FileType::Docx
+    } else if contains(b"xl/") {
+        FileType::Xlsx
+    } else if contains(b"ppt/") {
+        FileType::Pptx
+    } else {
+        FileType::Zip
+    }
+}
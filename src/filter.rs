@@ -0,0 +1,176 @@
+use std::path::Path;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::RegexSet;
+
+use crate::{
+    error::{Result, SkipReason},
+    file_type::Category,
+    options::RenameOptions,
+};
+
+/// Decides which files are selected before they are counted or renamed
+pub struct FileFilter {
+    extensions: ExtensionFilter,
+    include: Option<PatternSet>,
+    exclude: Option<PatternSet>,
+    categories: Option<Vec<Category>>,
+}
+
+impl FileFilter {
+    pub fn new(options: &RenameOptions) -> Result<Self> {
+        Ok(FileFilter {
+            extensions: ExtensionFilter::new(
+                options.selected_extensions.clone(),
+                &options.extensions,
+                &options.exclude_extensions,
+            ),
+            include: PatternSet::new(&options.include, options.regex)?,
+            exclude: PatternSet::new(&options.exclude, options.regex)?,
+            categories: options.categories.clone(),
+        })
+    }
+
+    /// `relative_path` is the path of the file relative to the source folder
+    pub fn check(
+        &self,
+        relative_path: &Path,
+        file_name: &str,
+        file_extension: &str,
+    ) -> Result<(), SkipReason> {
+        if !self.extensions.matches(file_name, file_extension) {
+            return Err(SkipReason::Extension);
+        }
+        if let Some(exclude) = &self.exclude {
+            if exclude.is_match(relative_path) {
+                return Err(SkipReason::Pattern);
+            }
+        }
+        match &self.include {
+            Some(include) if !include.is_match(relative_path) => Err(SkipReason::Pattern),
+            _ => Ok(()),
+        }
+    }
+
+    /// Only the exclusions, for sidecars that follow the file they belong to
+    pub fn check_excluded(&self, relative_path: &Path, file_name: &str) -> Result<(), SkipReason> {
+        if self.extensions.excludes(file_name) {
+            return Err(SkipReason::Extension);
+        }
+        match &self.exclude {
+            Some(exclude) if exclude.is_match(relative_path) => Err(SkipReason::Pattern),
+            _ => Ok(()),
+        }
+    }
+
+    /// The longest `--ext` extension the name ends with, as written in the name
+    ///
+    /// Multi-part extensions like `tar.gz` are kept together when the date is added.
+    pub fn matched_extension<'a>(&self, file_name: &'a str) -> Option<&'a str> {
+        self.extensions
+            .include
+            .iter()
+            .filter(|extension| has_extension(file_name, extension))
+            .max_by_key(|extension| extension.len())
+            .and_then(|extension| file_name.get(file_name.len() - extension.len()..))
+    }
+
+    /// Files picked by extension or with `--all` are renamed regardless of their category
+    pub fn check_category(&self, category: Option<Category>) -> Result<(), SkipReason> {
+        match &self.categories {
+            Some(categories)
+                if !category.is_some_and(|category| categories.contains(&category)) =>
+            {
+                Err(SkipReason::Category)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Extensions picked with `--extension`, selected with `--ext` and skipped with `--exclude-ext`
+struct ExtensionFilter {
+    selections: Option<Vec<String>>,
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl ExtensionFilter {
+    fn new(selections: Option<Vec<String>>, include: &[String], exclude: &[String]) -> Self {
+        ExtensionFilter {
+            selections,
+            include: normalize_extensions(include),
+            exclude: normalize_extensions(exclude),
+        }
+    }
+
+    fn matches(&self, file_name: &str, file_extension: &str) -> bool {
+        if let Some(selections) = &self.selections {
+            if !selections
+                .iter()
+                .any(|selection| selection == file_extension)
+            {
+                return false;
+            }
+        }
+        if self.excludes(file_name) {
+            return false;
+        }
+        self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|extension| has_extension(file_name, extension))
+    }
+
+    fn excludes(&self, file_name: &str) -> bool {
+        self.exclude
+            .iter()
+            .any(|extension| has_extension(file_name, extension))
+    }
+}
+
+/// Patterns from `--include` and `--exclude`, as globs or with `--regex` as regular expressions
+enum PatternSet {
+    Glob(GlobSet),
+    Regex(RegexSet),
+}
+
+impl PatternSet {
+    fn new(patterns: &[String], regex: bool) -> Result<Option<Self>> {
+        if patterns.is_empty() {
+            return Ok(None);
+        }
+        if regex {
+            return Ok(Some(PatternSet::Regex(RegexSet::new(patterns)?)));
+        }
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            builder.add(Glob::new(pattern)?);
+        }
+        Ok(Some(PatternSet::Glob(builder.build()?)))
+    }
+
+    fn is_match(&self, relative_path: &Path) -> bool {
+        match self {
+            PatternSet::Glob(globs) => globs.is_match(relative_path),
+            PatternSet::Regex(regexes) => regexes.is_match(&relative_path.to_string_lossy()),
+        }
+    }
+}
+
+fn normalize_extensions(extensions: &[String]) -> Vec<String> {
+    extensions
+        .iter()
+        .map(|extension| extension.trim().trim_start_matches('.').to_lowercase())
+        .filter(|extension| !extension.is_empty())
+        .collect()
+}
+
+/// Case-insensitive check that also handles multi-part extensions like `tar.gz`
+fn has_extension(file_name: &str, extension: &str) -> bool {
+    file_name
+        .to_lowercase()
+        .strip_suffix(extension)
+        .is_some_and(|rest| rest.ends_with('.'))
+}
@@ -0,0 +1,338 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs::{self, File},
+    hash::{DefaultHasher, Hash, Hasher},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::{
+    content_id::content_identifier, date_source::DateSources, file_type::Category, HEADER_LENGTH,
+};
+
+/// Extensions of files that hold edits or metadata for the file with the same name
+pub const SIDECAR_EXTENSIONS: &[&str] = &["xmp", "aae", "thm", "pp3", "dop", "on1", "srt"];
+
+/// Seconds between the dates of files with the same name that are still taken together,
+/// files further apart were only named alike, e.g. after the camera's counter wrapped
+const PAIR_WINDOW_SECONDS: i64 = 60;
+
+/// Raw camera formats, which are preferred over a JPEG taken at the same time
+const RAW_EXTENSIONS: &[&str] = &[
+    "3fr", "arw", "cr2", "cr3", "crw", "dng", "erf", "iiq", "kdc", "mos", "mrw", "nef", "nrw",
+    "orf", "pef", "raf", "raw", "rw2", "rwl", "sr2", "srf", "srw", "x3f",
+];
+
+/// Files renamed together, which all get the new name of the first one
+#[derive(Debug)]
+pub(crate) struct FileGroup {
+    /// The primary file first, then files taken at the same time like the JPEG of a raw
+    /// file or the video of a Live Photo
+    pub(crate) files: Vec<PathBuf>,
+    pub(crate) sidecars: Vec<PathBuf>,
+}
+
+impl FileGroup {
+    fn single(file: PathBuf) -> Self {
+        FileGroup {
+            files: vec![file],
+            sidecars: vec![],
+        }
+    }
+
+    pub(crate) fn primary(&self) -> &Path {
+        &self.files[0]
+    }
+}
+
+/// Extensions of Live Photo videos and images
+const LIVE_PHOTO_VIDEOS: &[&str] = &["mov", "qt", "mp4"];
+const LIVE_PHOTO_IMAGES: &[&str] = &["heic", "heif", "hif", "jpg", "jpeg"];
+
+/// Files that can belong together: in the same folder, with the same name up to its first dot
+///
+/// The files are counted in a first listing. In the second one a bucket is grouped as soon
+/// as its last file comes up, so only buckets still missing files are held. The counts
+/// take about 16 bytes per name, far less than the paths themselves.
+#[derive(Debug, Default)]
+pub(crate) struct Buckets {
+    counts: HashMap<u64, BucketCount>,
+    pending: HashMap<u64, Vec<PathBuf>>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct BucketCount {
+    files: u32,
+    live_photo_images: bool,
+    live_photo_videos: bool,
+}
+
+impl Buckets {
+    /// Count a file of the first listing
+    pub(crate) fn count(&mut self, path: &Path) {
+        let count = self.counts.entry(bucket_key(path)).or_default();
+        count.files += 1;
+        count.live_photo_images |= has_extension(path, LIVE_PHOTO_IMAGES);
+        count.live_photo_videos |= has_extension(path, LIVE_PHOTO_VIDEOS);
+    }
+
+    /// Whether files with the name of this one were counted
+    pub(crate) fn counted(&self, path: &Path) -> bool {
+        self.counts.contains_key(&bucket_key(path))
+    }
+
+    /// Whether a Live Photo video has no image with the same name, so images have to be
+    /// read to find the one it belongs to
+    pub(crate) fn lone_videos(&self) -> bool {
+        self.counts
+            .values()
+            .any(|count| count.live_photo_videos && !count.live_photo_images)
+    }
+
+    /// Add a file of the second listing, its bucket is returned once it's complete
+    ///
+    /// Files that weren't counted, e.g. ones that arrived in between, come on their own.
+    pub(crate) fn add(&mut self, path: PathBuf) -> Option<Vec<PathBuf>> {
+        let key = bucket_key(&path);
+        let expected = self.counts.get(&key).map_or(1, |count| count.files);
+        let bucket = self.pending.entry(key).or_default();
+        bucket.push(path);
+        if bucket.len() < expected as usize {
+            return None;
+        }
+        self.counts.remove(&key);
+        self.pending.remove(&key)
+    }
+
+    /// Buckets with files that were gone by the second listing
+    pub(crate) fn rest(self) -> impl Iterator<Item = Vec<PathBuf>> {
+        self.pending.into_values()
+    }
+}
+
+/// Names are only hashed, a collision just puts two buckets together
+fn bucket_key(path: &Path) -> u64 {
+    let name = lowercase(path.file_name()).unwrap_or_default();
+    let mut hasher = DefaultHasher::new();
+    folder(path).hash(&mut hasher);
+    name.split('.').next().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Which file of a pair is the primary one, lower comes first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Rank {
+    Raw,
+    Image,
+    Video,
+}
+
+impl Rank {
+    fn of(path: &Path) -> Option<Rank> {
+        let extension = lowercase(path.extension())?;
+        if RAW_EXTENSIONS.contains(&extension.as_str()) {
+            return Some(Rank::Raw);
+        }
+        let mime = mime_guess::from_ext(&extension).first()?;
+        match Category::from_mime(mime.essence_str())? {
+            Category::Image => Some(Rank::Image),
+            Category::Video => Some(Rank::Video),
+            _ => None,
+        }
+    }
+}
+
+/// Group files that belong together so they get the same new name
+///
+/// Images and videos with the same name in a folder are paired, e.g. `DSC_0001.NEF` with
+/// `DSC_0001.JPG` or `IMG_0001.HEIC` with `IMG_0001.MOV`, when they have the same content
+/// identifier or were dated within [`PAIR_WINDOW_SECONDS`] of each other. Videos are only
+/// paired with `live_photos`, those with another name are left alone, see [`live_photo_video`].
+///
+/// With `sidecars`, `IMG_1234.xmp` and `IMG_1234.CR2.xmp` are added to the group of
+/// `IMG_1234.CR2`. Sidecars without a matching file in the same folder are renamed on
+/// their own.
+pub(crate) fn group_files(
+    mut paths: Vec<PathBuf>,
+    sidecars: bool,
+    live_photos: bool,
+    date_sources: &DateSources,
+) -> Vec<FileGroup> {
+    paths.sort();
+    let (sidecar_files, files): (Vec<_>, Vec<_>) = paths
+        .into_iter()
+        .partition(|path| sidecars && is_sidecar(path));
+
+    let mut groups: Vec<FileGroup> = vec![];
+    let mut pairs: HashMap<(PathBuf, String), usize> = HashMap::new();
+    let mut dates = HashMap::new();
+    for file in files {
+        let key = Rank::of(&file)
+            .filter(|&rank| live_photos || rank != Rank::Video)
+            .and(lowercase(file.file_stem()))
+            .map(|stem| (folder(&file).to_path_buf(), stem));
+        let pair = key
+            .as_ref()
+            .and_then(|key| pairs.get(key))
+            .filter(|&&index| {
+                groups[index]
+                    .files
+                    .iter()
+                    .all(|other| taken_together(other, &file, date_sources, &mut dates))
+            });
+        match pair {
+            Some(&index) => groups[index].files.push(file),
+            None => {
+                if let Some(key) = key {
+                    pairs.insert(key, groups.len());
+                }
+                groups.push(FileGroup::single(file));
+            }
+        }
+    }
+    for group in &mut groups {
+        group.files.sort_by_key(|file| Rank::of(file));
+    }
+
+    let mut by_name: HashMap<(&Path, String), usize> = HashMap::new();
+    let mut by_stem: HashMap<(&Path, String), usize> = HashMap::new();
+    for (index, group) in groups.iter().enumerate() {
+        for file in &group.files {
+            if let Some(name) = lowercase(file.file_name()) {
+                by_name.insert((folder(file), name), index);
+            }
+            if let Some(stem) = lowercase(file.file_stem()) {
+                by_stem.entry((folder(file), stem)).or_insert(index);
+            }
+        }
+    }
+
+    let mut companions: Vec<(usize, PathBuf)> = vec![];
+    let mut unmatched = vec![];
+    for sidecar in sidecar_files {
+        let primary = lowercase(sidecar.file_stem()).and_then(|stem| {
+            let key = (folder(&sidecar), stem);
+            by_name.get(&key).or_else(|| by_stem.get(&key)).copied()
+        });
+        match primary {
+            Some(index) => companions.push((index, sidecar)),
+            None => unmatched.push(sidecar),
+        }
+    }
+    for (index, sidecar) in companions {
+        groups[index].sidecars.push(sidecar);
+    }
+    groups.extend(unmatched.into_iter().map(FileGroup::single));
+    groups
+}
+
+/// Files with the same name belong together when the iPhone gave them the same content
+/// identifier, or when they are dated close to each other
+fn taken_together(
+    a: &Path,
+    b: &Path,
+    date_sources: &DateSources,
+    dates: &mut HashMap<PathBuf, Option<DateTime<FixedOffset>>>,
+) -> bool {
+    if let (Some(a), Some(b)) = (content_identifier(a), content_identifier(b)) {
+        return a == b;
+    }
+    let mut date = |path: &Path| {
+        *dates
+            .entry(path.to_path_buf())
+            .or_insert_with(|| file_date(path, date_sources))
+    };
+    match (date(a), date(b)) {
+        (Some(a), Some(b)) => (a - b).num_seconds().abs() <= PAIR_WINDOW_SECONDS,
+        _ => false,
+    }
+}
+
+fn file_date(path: &Path, date_sources: &DateSources) -> Option<DateTime<FixedOffset>> {
+    let metadata = fs::metadata(path).ok()?;
+    let mut header = Vec::with_capacity(HEADER_LENGTH);
+    File::open(path)
+        .ok()?
+        .take(HEADER_LENGTH as u64)
+        .read_to_end(&mut header)
+        .ok()?;
+    Some(date_sources.find_date(path, &metadata, &header)?.date)
+}
+
+/// The folder and content identifier of a Live Photo video without an image of the same name
+///
+/// The iPhone writes the identifier into both halves, so the video can follow its image
+/// when that was named differently.
+pub(crate) fn live_photo_video(group: &FileGroup) -> Option<(PathBuf, String)> {
+    let [video] = group.files.as_slice() else {
+        return None;
+    };
+    if !has_extension(video, LIVE_PHOTO_VIDEOS) {
+        return None;
+    }
+    Some((folder(video).to_path_buf(), content_identifier(video)?))
+}
+
+/// The folder and content identifier of a Live Photo image without a video of the same name
+pub(crate) fn live_photo_image(group: &FileGroup) -> Option<(PathBuf, String)> {
+    let image = group.primary();
+    if !has_extension(image, LIVE_PHOTO_IMAGES)
+        || group
+            .files
+            .iter()
+            .any(|file| Rank::of(file) == Some(Rank::Video))
+    {
+        return None;
+    }
+    Some((folder(image).to_path_buf(), content_identifier(image)?))
+}
+
+/// The file a sidecar like `IMG_1234.CR2.xmp` was written for, by its whole name
+pub(crate) fn sidecar_owner<'a>(sidecar: &Path, files: &'a [PathBuf]) -> Option<&'a Path> {
+    let stem = lowercase(sidecar.file_stem())?;
+    files
+        .iter()
+        .find(|file| lowercase(file.file_name()).is_some_and(|name| name == stem))
+        .map(PathBuf::as_path)
+}
+
+/// What follows the primary file's stem in a companion's name, e.g. `.CR2.xmp`
+pub(crate) fn companion_suffix(primary: &Path, companion: &Path) -> String {
+    let primary_stem = primary
+        .file_stem()
+        .and_then(OsStr::to_str)
+        .unwrap_or_default();
+    let companion_name = companion
+        .file_name()
+        .and_then(OsStr::to_str)
+        .unwrap_or_default();
+    match companion_name.get(..primary_stem.len()) {
+        Some(stem) if stem.eq_ignore_ascii_case(primary_stem) => {
+            companion_name[primary_stem.len()..].to_owned()
+        }
+        _ => companion
+            .extension()
+            .and_then(OsStr::to_str)
+            .map(|extension| format!(".{extension}"))
+            .unwrap_or_default(),
+    }
+}
+
+fn is_sidecar(path: &Path) -> bool {
+    has_extension(path, SIDECAR_EXTENSIONS)
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    lowercase(path.extension()).is_some_and(|extension| extensions.contains(&&*extension))
+}
+
+fn folder(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new(""))
+}
+
+fn lowercase(name: Option<&OsStr>) -> Option<String> {
+    name.and_then(OsStr::to_str).map(str::to_lowercase)
+}
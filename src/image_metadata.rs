@@ -0,0 +1,243 @@
+use std::{
+    fs::{File, Metadata},
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+
+use crate::{
+    date_source::{Confidence, DateSource, FoundDate},
+    exif::{self, Exif},
+    file_type::{sniff, FileType},
+    xmp,
+};
+
+/// Bytes read from the start of TIFF based files, their EXIF data is near the start
+const TIFF_LENGTH: u64 = 1024 * 1024;
+
+/// Bytes read from the preview JPEG in RAF files
+const RAF_JPEG_LENGTH: u64 = 256 * 1024;
+
+/// Largest PNG or WebP chunk that is read for metadata
+const MAX_CHUNK_LENGTH: u32 = 16 * 1024 * 1024;
+
+/// The date in the metadata of JPEG, TIFF, raw, PNG and WebP images
+///
+/// EXIF is read from all of them. PNG files can also have a `tIME` chunk, a
+/// `Creation Time` text chunk and XMP in an `iTXt` chunk, WebP files an `XMP ` chunk.
+pub struct ImageMetadata;
+
+impl DateSource for ImageMetadata {
+    fn name(&self) -> &str {
+        "image metadata"
+    }
+
+    fn find_date(&self, path: &Path, _metadata: &Metadata, header: &[u8]) -> Option<FoundDate> {
+        match sniff(header)? {
+            FileType::Png => png_date(path),
+            FileType::Webp => webp_date(path),
+            _ => read_exif(path, header)?.date(),
+        }
+    }
+}
+
+/// EXIF of JPEG, TIFF and raw files, PNG and WebP chunks are read by [`ImageMetadata`]
+pub(crate) fn read_exif(path: &Path, header: &[u8]) -> Option<Exif> {
+    match sniff(header)? {
+        FileType::Jpeg => jpeg_exif(path, header, 0),
+        FileType::Tiff | FileType::Orf | FileType::Rw2 => {
+            Exif::parse(&read_range(path, 0, TIFF_LENGTH)?)
+        }
+        FileType::Raf => {
+            // The big endian offset of the preview JPEG, which holds the EXIF data
+            let offset = u32::from_be_bytes(header.get(84..88)?.try_into().ok()?);
+            let jpeg = read_range(path, offset.into(), RAF_JPEG_LENGTH)?;
+            jpeg_exif(path, &jpeg, offset.into())
+        }
+        _ => None,
+    }
+}
+
+/// EXIF from the APP1 segment of a JPEG that starts at `offset` in the file
+///
+/// `data` is the start of the JPEG, more is read when the segment doesn't fit.
+fn jpeg_exif(path: &Path, data: &[u8], offset: u64) -> Option<Exif> {
+    let mut position = 2;
+    loop {
+        let marker = *data.get(position + 1)?;
+        if *data.get(position)? != 0xFF || marker == 0xDA || marker == 0xD9 {
+            return None;
+        }
+        // The length counts its own two bytes
+        let length = usize::from(u16::from_be_bytes(
+            data.get(position + 2..position + 4)?.try_into().ok()?,
+        ));
+        if length < 2 {
+            return None;
+        }
+        let segment = position + 4..position + 2 + length;
+        if marker == 0xE1 && data.get(position + 4..position + 10) == Some(b"Exif\0\0") {
+            if let Some(segment_data) = data.get(segment.clone()) {
+                return Exif::parse(segment_data);
+            }
+            let segment_data =
+                read_range(path, offset + segment.start as u64, (length - 2) as u64)?;
+            return Exif::parse(&segment_data);
+        }
+        position = segment.end;
+    }
+}
+
+fn png_date(path: &Path) -> Option<FoundDate> {
+    let chunks = png_chunks(path, &[b"eXIf", b"tEXt", b"iTXt", b"tIME"])?;
+    let dates = chunks.iter().filter_map(|(kind, data)| match kind {
+        b"eXIf" => Exif::parse(data).and_then(|exif| exif.date()),
+        b"tIME" => png_time(data),
+        _ => png_text_date(kind, data),
+    });
+    most_trusted(dates.collect())
+}
+
+/// EXIF from the `eXIf` chunk of a PNG file
+pub(crate) fn png_exif(path: &Path) -> Option<Exif> {
+    png_chunks(path, &[b"eXIf"])?
+        .iter()
+        .find_map(|(_, data)| Exif::parse(data))
+}
+
+/// The chunks of these kinds in a PNG file, others are skipped without reading them
+fn png_chunks(path: &Path, kinds: &[&[u8; 4]]) -> Option<Vec<([u8; 4], Vec<u8>)>> {
+    let mut file = File::open(path).ok()?;
+    file.seek(SeekFrom::Start(8)).ok()?;
+    let mut chunks = vec![];
+    loop {
+        let mut header = [0; 8];
+        if file.read_exact(&mut header).is_err() {
+            break;
+        }
+        let length = u32::from_be_bytes(header[..4].try_into().ok()?);
+        let kind: [u8; 4] = header[4..].try_into().ok()?;
+        if &kind == b"IEND" {
+            break;
+        }
+        if !kinds.contains(&&kind) || length > MAX_CHUNK_LENGTH {
+            // Skip the data and the CRC after it
+            file.seek(SeekFrom::Current(i64::from(length) + 4)).ok()?;
+            continue;
+        }
+        let mut data = vec![0; length as usize];
+        file.read_exact(&mut data).ok()?;
+        file.seek(SeekFrom::Current(4)).ok()?;
+        chunks.push((kind, data));
+    }
+    Some(chunks)
+}
+
+/// `tIME` is the UTC time the image was last changed
+fn png_time(data: &[u8]) -> Option<FoundDate> {
+    let [year_high, year_low, month, day, hour, minute, second] = *data else {
+        return None;
+    };
+    let date = NaiveDate::from_ymd_opt(
+        u16::from_be_bytes([year_high, year_low]).into(),
+        month.into(),
+        day.into(),
+    )?
+    .and_hms_opt(hour.into(), minute.into(), second.into())?;
+    let date: DateTime<Local> = date.and_utc().into();
+    Some(FoundDate::new(date.fixed_offset(), Confidence::Medium))
+}
+
+/// `Creation Time` in a `tEXt` or uncompressed `iTXt` chunk, or XMP in an `iTXt` chunk
+fn png_text_date(kind: &[u8], data: &[u8]) -> Option<FoundDate> {
+    let (keyword, rest) = split_at_nul(data)?;
+    let text = if kind == b"iTXt" {
+        // Compression flag and method, then the language and translated keyword
+        let (&compressed, rest) = rest.split_first()?;
+        if compressed != 0 {
+            return None;
+        }
+        let (_language, rest) = split_at_nul(rest.get(1..)?)?;
+        let (_translated, text) = split_at_nul(rest)?;
+        String::from_utf8_lossy(text)
+    } else {
+        // tEXt is Latin-1, which matches UTF-8 for the characters in dates
+        String::from_utf8_lossy(rest)
+    };
+    match keyword {
+        b"Creation Time" => Some(FoundDate::new(parse_text_date(&text)?, Confidence::Medium)),
+        b"XML:com.adobe.xmp" => xmp::packet_date(&xmp::find_packet(text.as_bytes())?),
+        _ => None,
+    }
+}
+
+/// Dates in text chunks, written as RFC 1123, ISO 8601 or EXIF dates
+fn parse_text_date(text: &str) -> Option<DateTime<chrono::FixedOffset>> {
+    let text = text.trim();
+    DateTime::parse_from_rfc2822(text)
+        .ok()
+        .or_else(|| xmp::parse_date(text))
+        .or_else(|| xmp::parse_date(&text.replacen(' ', "T", 1)))
+        .or_else(|| exif::parse_date(text, None))
+        .or_else(|| {
+            // Some software writes UTC without a zone, e.g. `2024-07-17 14:30:00 UTC`
+            let utc = text.strip_suffix("UTC")?.trim().replacen(' ', "T", 1);
+            let date = chrono::NaiveDateTime::parse_from_str(&utc, "%Y-%m-%dT%H:%M:%S").ok()?;
+            Some(DateTime::<Utc>::from_naive_utc_and_offset(date, Utc).fixed_offset())
+        })
+}
+
+fn webp_date(path: &Path) -> Option<FoundDate> {
+    let mut file = File::open(path).ok()?;
+    file.seek(SeekFrom::Start(12)).ok()?;
+    let mut dates = vec![];
+    loop {
+        let mut header = [0; 8];
+        if file.read_exact(&mut header).is_err() {
+            break;
+        }
+        let length = u32::from_le_bytes(header[4..].try_into().ok()?);
+        // Chunks are padded to an even length
+        let padded = i64::from(length) + i64::from(length % 2);
+        let kind = &header[..4];
+        if !matches!(kind, b"EXIF" | b"XMP ") || length > MAX_CHUNK_LENGTH {
+            file.seek(SeekFrom::Current(padded)).ok()?;
+            continue;
+        }
+        let mut data = vec![0; length as usize];
+        file.read_exact(&mut data).ok()?;
+        file.seek(SeekFrom::Current(padded - i64::from(length)))
+            .ok()?;
+        let found = match kind {
+            b"EXIF" => Exif::parse(&data).and_then(|exif| exif.date()),
+            _ => xmp::packet_date(&xmp::find_packet(data.as_slice())?),
+        };
+        dates.extend(found);
+    }
+    most_trusted(dates)
+}
+
+/// The date with the highest confidence, the first one wins ties
+fn most_trusted(dates: Vec<FoundDate>) -> Option<FoundDate> {
+    dates.into_iter().reduce(|best, found| {
+        if found.confidence > best.confidence {
+            found
+        } else {
+            best
+        }
+    })
+}
+
+fn split_at_nul(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let nul = data.iter().position(|&byte| byte == 0)?;
+    Some((&data[..nul], &data[nul + 1..]))
+}
+
+fn read_range(path: &Path, start: u64, length: u64) -> Option<Vec<u8>> {
+    let mut file = File::open(path).ok()?;
+    file.seek(SeekFrom::Start(start)).ok()?;
+    let mut data = vec![];
+    file.take(length).read_to_end(&mut data).ok()?;
+    Some(data)
+}
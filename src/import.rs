@@ -0,0 +1,335 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::{Command, Output},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{bail, Context, Result};
+use indicatif::ProgressBar;
+use owo_colors::OwoColorize;
+use tokio::{
+    fs::{self, read_dir, OpenOptions},
+    io::AsyncWriteExt,
+    task::spawn_blocking,
+};
+
+use crate::{args::ImportArgs, Error, FileStatus, RenameOptions, Renamer};
+
+/// Imported files, kept in the library so cards can be imported again without duplicates
+pub const STATE_FILE: &str = ".createdat-imported";
+
+/// Library layout used when `--folders` isn't set
+const DEFAULT_FOLDERS: &str = "%Y/%Y-%m-%d";
+
+/// Copy the files on a card that weren't imported before into dated folders in the target
+///
+/// Copies are verified by hashing both files before they are recorded in the state
+/// file, and only verified files are deleted with `--clear`.
+pub async fn run(
+    mut options: RenameOptions,
+    import_args: &ImportArgs,
+    progress_bar: &ProgressBar,
+) -> Result<()> {
+    let card = PathBuf::from(import_args.card.trim());
+    if options.folder_format.is_none() {
+        options = options.folder_format(DEFAULT_FOLDERS);
+    }
+    let preview = options.preview;
+    let library = options.target.clone();
+    let renamer = Renamer::new(options.source(&card));
+
+    let state = ImportState::read(&library).await?;
+    let mut new_files = vec![];
+    let mut imported_before = vec![];
+    for file in card_files(&card).await? {
+        let key = file_key(&file, import_args.hash).await?;
+        match state.destinations.get(&key) {
+            Some(destination) => imported_before.push((file, library.join(destination))),
+            None => new_files.push((key, file)),
+        }
+    }
+    let keys: HashMap<PathBuf, String> = new_files
+        .into_iter()
+        .map(|(key, file)| (file, key))
+        .collect();
+
+    let report = renamer.rename_paths(keys.keys().cloned()).await;
+    progress_bar.finish_and_clear();
+    let report = report?;
+    if preview {
+        println!("{} Files were imported before", imported_before.len());
+        return Ok(());
+    }
+
+    let mut imported = vec![];
+    let mut failed = report.failed.len();
+    for file in &report.files {
+        let copied = match &file.status {
+            FileStatus::Renamed => true,
+            // Left over from an import that wasn't recorded, fine if it's the same file
+            FileStatus::Failed(Error::Conflict { .. }) => false,
+            FileStatus::Failed(_) | FileStatus::Previewed => {
+                failed += 1;
+                continue;
+            }
+        };
+        // Every renamed file should be one of the new files, but never trust it blindly
+        let Some(key) = keys.get(&file.source) else {
+            failed += 1;
+            continue;
+        };
+        if same_contents(&file.source, &file.destination).await? {
+            imported.push((key.clone(), file));
+        } else if copied {
+            failed += 1;
+            eprintln!(
+                "{} {} {}",
+                " ERROR ".black().on_red(),
+                file.destination.display().blue(),
+                "doesn't match the file on the card".red()
+            );
+        } else {
+            failed += 1;
+        }
+    }
+    ImportState::append(
+        &library,
+        imported
+            .iter()
+            .map(|(key, file)| (key.as_str(), file.destination.as_path())),
+    )
+    .await?;
+
+    println!(
+        "{} {} {} were imported before",
+        imported.len().green(),
+        "Files imported,".green(),
+        imported_before.len()
+    );
+
+    if !import_args.clear && !import_args.eject {
+        return Ok(());
+    }
+    if failed > 0 {
+        bail!("{failed} files were not imported, the card was left as it is");
+    }
+    if import_args.clear {
+        let mut cleared = 0;
+        let verified_before = {
+            let mut verified = vec![];
+            for (file, destination) in &imported_before {
+                if same_contents(file, destination).await? {
+                    verified.push(file);
+                } else {
+                    eprintln!(
+                        "{} {} {}",
+                        " WARNING ".black().on_yellow(),
+                        file.display().blue(),
+                        "was imported before but its copy is missing or different. Keeping it."
+                            .yellow()
+                    );
+                }
+            }
+            verified
+        };
+        for file in imported
+            .iter()
+            .map(|(_, file)| &file.source)
+            .chain(verified_before)
+        {
+            fs::remove_file(file)
+                .await
+                .with_context(|| format!("deleting {}", file.display()))?;
+            cleared += 1;
+        }
+        println!("{} Files deleted from the card", cleared);
+    }
+    if import_args.eject {
+        eject(&card)?;
+        println!("{} {}", card.display().blue(), "can be removed".green());
+    }
+    Ok(())
+}
+
+/// Files in the DCIM folders (e.g. `DCIM/100CANON`), or at the top of the card without one
+async fn card_files(card: &Path) -> Result<Vec<PathBuf>> {
+    let dcim = card.join("DCIM");
+    let mut folders = vec![];
+    if fs::metadata(&dcim).await.is_ok_and(|dcim| dcim.is_dir()) {
+        let mut entries = read_dir(&dcim).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                folders.push(entry.path());
+            }
+        }
+        folders.push(dcim);
+    } else {
+        folders.push(card.to_path_buf());
+    }
+
+    let mut files = vec![];
+    for folder in folders {
+        let mut entries = read_dir(&folder)
+            .await
+            .with_context(|| format!("reading {}", folder.display()))?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_file() {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// `size/modified/name`, or the hash of the contents with `--hash`
+async fn file_key(file: &Path, hash: bool) -> Result<String> {
+    if hash {
+        return Ok(format!("blake3/{}", hash_file(file.to_path_buf()).await?));
+    }
+    let metadata = fs::metadata(file).await?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    Ok(format!("{}/{}/{}", metadata.len(), modified, name))
+}
+
+async fn hash_file(file: PathBuf) -> io::Result<blake3::Hash> {
+    spawn_blocking(move || {
+        let mut hasher = blake3::Hasher::new();
+        let mut file = File::open(file)?;
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        Ok(hasher.finalize())
+    })
+    .await?
+}
+
+async fn same_contents(file: &Path, copy: &Path) -> Result<bool> {
+    if fs::metadata(copy).await.is_err() {
+        return Ok(false);
+    }
+    let (file_hash, copy_hash) =
+        tokio::try_join!(hash_file(file.to_path_buf()), hash_file(copy.to_path_buf()))?;
+    Ok(file_hash == copy_hash)
+}
+
+/// Lines of a file key and the path it was copied to in the library, separated by a tab
+struct ImportState {
+    destinations: HashMap<String, PathBuf>,
+}
+
+impl ImportState {
+    async fn read(library: &Path) -> Result<ImportState> {
+        let path = library.join(STATE_FILE);
+        let content = match fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+        };
+        let destinations = content
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(key, destination)| (key.to_owned(), PathBuf::from(destination)))
+            .collect();
+        Ok(ImportState { destinations })
+    }
+
+    async fn append<'a>(
+        library: &Path,
+        imported: impl Iterator<Item = (&'a str, &'a Path)>,
+    ) -> Result<()> {
+        let path = library.join(STATE_FILE);
+        let lines: String = imported
+            .map(|(key, destination)| {
+                let destination = destination.strip_prefix(library).unwrap_or(destination);
+                format!("{}\t{}\n", key, destination.display())
+            })
+            .collect();
+        if lines.is_empty() {
+            return Ok(());
+        }
+        let mut state = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await
+            .with_context(|| format!("opening {}", path.display()))?;
+        state.write_all(lines.as_bytes()).await?;
+        Ok(())
+    }
+}
+
+fn eject(card: &Path) -> Result<()> {
+    if cfg!(target_os = "macos") {
+        let output = Command::new("diskutil")
+            .arg("eject")
+            .arg(card)
+            .output()
+            .with_context(|| format!("ejecting {}", card.display()))?;
+        return check_ejected(card, &output);
+    }
+    // `umount` needs root for most cards, udisks unmounts the ones the desktop mounted
+    // for the user and powers them off
+    if let Some(device) = mount_device(card) {
+        match Command::new("udisksctl")
+            .args(["unmount", "--block-device"])
+            .arg(&device)
+            .output()
+        {
+            Ok(output) => {
+                check_ejected(card, &output)?;
+                // Built-in card readers can't be powered off, the card is unmounted anyway
+                let _ = Command::new("udisksctl")
+                    .args(["power-off", "--block-device"])
+                    .arg(&device)
+                    .output();
+                return Ok(());
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err).with_context(|| format!("ejecting {}", card.display()));
+            }
+        }
+    }
+    let output = Command::new("umount")
+        .arg(card)
+        .output()
+        .with_context(|| format!("ejecting {}", card.display()))?;
+    check_ejected(card, &output)
+}
+
+/// The device mounted at `card`, e.g. `/dev/sdb1`
+fn mount_device(card: &Path) -> Option<String> {
+    let output = Command::new("findmnt")
+        .args(["--noheadings", "--output", "SOURCE", "--target"])
+        .arg(card)
+        .output()
+        .ok()?;
+    let device = String::from_utf8(output.stdout).ok()?.trim().to_owned();
+    (output.status.success() && device.starts_with("/dev/")).then_some(device)
+}
+
+fn check_ejected(card: &Path, output: &Output) -> Result<()> {
+    if !output.status.success() {
+        bail!(
+            "ejecting {} failed, the files were imported but the card has to be ejected by \
+             hand: {}",
+            card.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
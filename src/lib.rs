@@ -1,43 +1,69 @@
 mod args;
+mod audio_metadata;
+mod config;
+mod content_id;
+mod date_range;
+mod date_source;
+mod document_metadata;
+mod error;
+mod exif;
+mod file_type;
+mod filter;
+mod group;
+mod image_metadata;
+mod import;
+mod media_info;
+mod options;
+mod places;
+mod progress;
+mod quicktime;
+mod renamer;
+mod takeout;
+mod video_metadata;
+mod watch;
+mod xmp;
 
 use std::{
     env::current_dir,
     ffi::OsStr,
     path::{Path, PathBuf},
-    str::FromStr,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
     time::{Duration, SystemTime},
 };
 
-use anyhow::{anyhow, bail, Result};
-use args::Args;
-use chrono::{DateTime, Local};
-use clap::Parser;
+use anyhow::Result;
+use args::{Args, Command};
+use clap::{CommandFactory, FromArgMatches};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
 use inquire::MultiSelect;
-use mime_guess::Mime;
 use owo_colors::OwoColorize;
-use tokio::{
-    fs::{self, create_dir_all, read_dir, remove_dir, remove_dir_all, DirEntry, ReadDir},
-    sync::{Mutex, Semaphore},
-    task::JoinHandle,
-};
-
-static PERMITS: Semaphore = Semaphore::const_new(15);
+use tokio::fs::{read_dir, remove_dir, remove_file, ReadDir};
 
-struct CurrentFile {
-    user_added_name: String,
-    original_name: String,
-}
-
-#[derive(Debug, Clone, Copy)]
-struct FileCount {
-    renamed: u32,
-    total: u32,
-    duplicate: u32,
-}
+pub use audio_metadata::AudioMetadata;
+pub use date_source::{Confidence, DateSource, DateSources, FileModified, FoundDate};
+pub use document_metadata::DocumentMetadata;
+pub use error::{Error, SkipReason};
+pub use file_type::{Category, HEADER_LENGTH};
+pub use group::SIDECAR_EXTENSIONS;
+pub use image_metadata::ImageMetadata;
+pub use media_info::PLACEHOLDERS;
+pub use options::{DateFormat, RenameOptions};
+pub use progress::{ProgressListener, RenameEvent};
+pub use renamer::{
+    FailedFile, FileCount, FileStatus, RenameReport, RenamedFile, Renamer, SkippedFile,
+};
+pub use takeout::GoogleTakeout;
+pub use video_metadata::VideoMetadata;
+pub use xmp::Xmp;
 
 pub async fn run() -> anyhow::Result<()> {
-    let args = Arc::new(Args::parse());
+    let matches = config::add_negations(Args::command()).get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    config::apply(&mut args, &matches)?;
+    let args = Arc::new(args);
 
     let source_folder: PathBuf = if let Some(name) = args.source.as_deref() {
         PathBuf::from(name.trim())
@@ -49,69 +75,67 @@ pub async fn run() -> anyhow::Result<()> {
     } else {
         PathBuf::from("renamed")
     };
-    let source_folder = Arc::new(source_folder);
 
-    let mut files = match read_dir(source_folder.as_ref()).await {
-        Ok(files) => files,
-        Err(err) => {
-            eprintln!(
-                "{} {}",
-                " ERROR READING DIRECTORY ".black().on_red(),
-                err.on_red()
-            );
-            return Err(err.into());
-        }
-    };
     let extension_selections = if args.extension {
-        (get_extensions(&mut files).await).unwrap_or_default()
+        let mut files = match read_dir(&source_folder).await {
+            Ok(files) => files,
+            Err(err) => {
+                eprintln!(
+                    "{} {}",
+                    " ERROR READING DIRECTORY ".black().on_red(),
+                    err.on_red()
+                );
+                return Err(err.into());
+            }
+        };
+        Some((get_extensions(&mut files).await).unwrap_or_default())
     } else {
-        vec![]
+        None
     };
+    let watching = matches!(args.command, Some(Command::Watch(_)));
+    let progress_bar = get_progress_bar(args.preview || watching);
+    let options = get_rename_options(&args, source_folder, renamed_folder, extension_selections)
+        .progress(CliProgress::new(progress_bar.clone(), args.preview));
+    match &args.command {
+        Some(Command::Watch(watch_args)) => return watch::run(options, watch_args).await,
+        Some(Command::Import(import_args)) => {
+            return import::run(options, import_args, &progress_bar).await
+        }
+        None => {}
+    }
+    let renamed_folder = Arc::new(options.target.clone());
 
     let start_time = SystemTime::now();
 
-    if !args.preview {
-        if let Err(err) = create_dir_all(renamed_folder.clone()).await {
+    let report = Renamer::new(options).run().await;
+    progress_bar.finish_and_clear();
+    let report = match report {
+        Ok(report) => report,
+        Err(err) => {
+            eprintln!("{} {}", " ERROR ".black().on_red(), err.red());
+            return Err(err.into());
+        }
+    };
+    let file_count = match report.count.duplicate {
+        0 => report.count,
+        count if count > 1 => {
             eprintln!(
-                "{} {}",
-                " ERROR CREATING DIRECTORY ".black().on_red(),
-                err.red()
+                "{} {} Duplicate names were skipped.",
+                " WARNING ".black().on_yellow(),
+                count.yellow()
             );
-            return Err(err.into());
+            return Ok(());
         }
-    }
-    let renamed_folder = Arc::new(renamed_folder);
-
-    let file_count = match copy_files(
-        files,
-        args.clone(),
-        renamed_folder.clone(),
-        extension_selections,
-    )
-    .await
-    {
-        Ok(file_count) => match file_count.duplicate {
-            0 => file_count,
-            count if count > 1 => {
-                eprintln!(
-                    "{} {} Duplicate names was skipped.",
-                    " WARNING ".black().on_yellow(),
-                    count.yellow()
-                );
-                return Ok(());
-            }
-            1 => {
-                eprintln!(
-                    "{} {} Duplicate names were skipped.",
-                    " WARNING ".black().on_yellow(),
-                    file_count.duplicate.yellow()
-                );
-                remove_dir_all(renamed_folder.as_ref()).await?;
-                return Ok(());
-            }
-            _ => return Ok(()),
-        },
-        Err(err) => return Err(err),
+        1 => {
+            eprintln!(
+                "{} {} Duplicate name was skipped.",
+                " WARNING ".black().on_yellow(),
+                report.count.duplicate.yellow()
+            );
+            remove_created(&report, renamed_folder.as_ref()).await?;
+            return Ok(());
+        }
+        _ => return Ok(()),
     };
     if args.preview {
         if file_count.duplicate > 0 {
@@ -128,98 +152,185 @@ pub async fn run() -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn copy_files(
-    mut files: ReadDir,
-    cli: Arc<Args>,
-    renamed_folder: Arc<PathBuf>,
-    extension_selections: Vec<String>,
-) -> Result<FileCount> {
-    let file_count = Arc::new(Mutex::new(FileCount {
-        renamed: 0,
-        total: 0,
-        duplicate: 0,
-    }));
-    let mut tasks: Vec<JoinHandle<anyhow::Result<()>>> = Vec::new();
+/// Undo a run, files that were in the target before are kept
+async fn remove_created(report: &RenameReport, target: &Path) -> Result<()> {
+    for file in &report.files {
+        if !matches!(file.status, FileStatus::Renamed) {
+            continue;
+        }
+        remove_file(&file.destination).await?;
+        // Folders made for the file go too, until one still has other files in it
+        let mut folder = file.destination.parent();
+        while let Some(current) = folder.filter(|folder| folder.starts_with(target)) {
+            if remove_dir(current).await.is_err() {
+                break;
+            }
+            folder = current.parent();
+        }
+    }
+    Ok(())
+}
 
-    while let Ok(Some(file)) = files.next_entry().await {
-        let extension_selections = extension_selections.clone();
-        let current_file = CurrentFile {
-            user_added_name: String::new(),
-            original_name: String::new(),
-        };
-        let file_count = file_count.clone();
-        let renamed_folder = renamed_folder.clone();
-        let cli = cli.clone();
-        let task = tokio::task::spawn(async move {
-            let _permit = PERMITS.acquire().await?;
-            let file_path = file.path();
+fn get_rename_options(
+    cli: &Args,
+    source_folder: PathBuf,
+    renamed_folder: PathBuf,
+    extension_selections: Option<Vec<String>>,
+) -> RenameOptions {
+    let date_format = if let Some(format) = &cli.format {
+        DateFormat::Custom(format.clone())
+    } else if cli.date {
+        DateFormat::Date
+    } else if cli.twelve {
+        DateFormat::TwelveHour
+    } else {
+        DateFormat::DateTime
+    };
+    let mut options = RenameOptions::new(source_folder)
+        .target(renamed_folder)
+        .date_in_front(cli.front)
+        .name_after_date(cli.suffix)
+        .keep_original_name(!cli.no_name)
+        .date_format(date_format)
+        .spaces(cli.space)
+        .fix_extension(cli.fix_extension)
+        .extensions(cli.ext.clone())
+        .exclude_extensions(cli.exclude_ext.clone())
+        .regex(cli.regex)
+        .sidecars(!cli.no_sidecars)
+        .preview(cli.preview);
+    if let Some(name) = &cli.name {
+        options = options.name(name.clone());
+    }
+    if let Some(folders) = &cli.folders {
+        options = options.folder_format(folders.clone());
+    }
+    if let Some(missing) = &cli.missing {
+        options = options.missing_field(missing.clone());
+    }
+    if let Some(places) = &cli.places {
+        options = options.places_file(places);
+    }
+    if let Some(jobs) = cli.jobs {
+        options = options.jobs(jobs);
+    }
+    if let Some(jobs) = cli.metadata_jobs {
+        options = options.metadata_jobs(jobs);
+    }
+    if let Some(jobs) = cli.copy_jobs {
+        options = options.copy_jobs(jobs);
+    }
+    if let Some(selections) = extension_selections {
+        options = options.selected_extensions(selections);
+    }
+    for pattern in &cli.include {
+        options = options.include(pattern.clone());
+    }
+    for pattern in &cli.exclude {
+        options = options.exclude(pattern.clone());
+    }
+    if let Some(since) = cli.since {
+        options = options.since(since);
+    }
+    if let Some(until) = cli.until {
+        options = options.until(until);
+    }
+    if cli.all {
+        options = options.all_files();
+    } else if !renames_images_only(cli) && cli.ext.is_empty() && !cli.extension {
+        options = options.categories(
+            [
+                (cli.images, Category::Image),
+                (cli.videos, Category::Video),
+                (cli.audio, Category::Audio),
+                (cli.documents, Category::Document),
+                (cli.archives, Category::Archive),
+            ]
+            .into_iter()
+            .filter_map(|(selected, category)| selected.then_some(category)),
+        );
+    }
+    options
+}
 
-            if file.metadata().await?.is_dir() {
-                return Ok(());
-            }
+fn get_progress_bar(preview: bool) -> ProgressBar {
+    if preview {
+        return ProgressBar::hidden();
+    }
+    let progress_bar = ProgressBar::with_draw_target(Some(0), ProgressDrawTarget::stderr());
+    progress_bar.set_style(
+        ProgressStyle::with_template(
+            "{spinner} [{elapsed_precise}] {wide_bar} {bytes}/{total_bytes} ({eta}) {msg}",
+        )
+        .unwrap_or_else(|_| ProgressStyle::default_bar()),
+    );
+    progress_bar.enable_steady_tick(Duration::from_millis(100));
+    progress_bar
+}
 
-            let image_destination = if let Ok(img) = get_image_destination(
-                cli.clone(),
-                &file,
-                current_file,
-                extension_selections,
-                &file_path,
-                &mut *file_count.lock().await,
-                renamed_folder,
-            )
-            .await
-            {
-                img
-            } else {
-                return Ok(());
-            };
-            if cli.preview {
-                println!("{}", image_destination.display());
-                return Ok(());
-            }
+/// Shows copied bytes and files on a progress bar, and prints warnings and errors above it
+struct CliProgress {
+    progress_bar: ProgressBar,
+    preview: bool,
+    planned: AtomicU64,
+    done: AtomicU64,
+}
 
-            if Path::new(&image_destination).exists() {
-                file_count.lock().await.duplicate += 1;
-                println!(
-                    "{} {} {}",
-                    " WARNING ".black().on_yellow(),
-                    &image_destination.display().blue(),
-                    "already exists. Skipping.".yellow()
-                );
-                return Ok(());
-            }
+impl CliProgress {
+    fn new(progress_bar: ProgressBar, preview: bool) -> Self {
+        CliProgress {
+            progress_bar,
+            preview,
+            planned: AtomicU64::new(0),
+            done: AtomicU64::new(0),
+        }
+    }
 
-            let max_retries: u8 = 3;
-            let retry_delay_ms: u64 = 100;
-            let mut attempt: u8 = 0;
+    fn update_message(&self) {
+        self.progress_bar.set_message(format!(
+            "{}/{} files",
+            self.done.load(Ordering::Relaxed),
+            self.planned.load(Ordering::Relaxed)
+        ));
+    }
+}
 
-            loop {
-                let copy_result = fs::copy(file.path(), image_destination.clone()).await;
-                if copy_result.is_ok() {
-                    file_count.lock().await.renamed += 1;
-                    break Ok(());
-                } else {
-                    attempt += 1;
-                    if attempt >= max_retries {
-                        eprintln!(
-                            "{}{}{:?}",
-                            " ERROR ".black().on_red(),
-                            " Max retries reached. Skipping file: ".red(),
-                            file.path().red()
-                        );
-                        break Ok(());
-                    }
-                    tokio::time::sleep(Duration::from_millis(retry_delay_ms)).await;
+impl ProgressListener for CliProgress {
+    fn on_event(&self, event: &RenameEvent<'_>) {
+        match event {
+            RenameEvent::Planned {
+                destination, size, ..
+            } => {
+                if self.preview {
+                    println!("{}", destination.display());
+                    return;
                 }
+                self.planned.fetch_add(1, Ordering::Relaxed);
+                self.progress_bar.inc_length(*size);
+                self.update_message();
             }
-        });
-        tasks.push(task);
-    }
-    for task in tasks {
-        task.await??;
+            RenameEvent::Copied { bytes, .. } => {
+                self.done.fetch_add(1, Ordering::Relaxed);
+                self.progress_bar.inc(*bytes);
+                self.update_message();
+            }
+            RenameEvent::Skipped { .. } => {}
+            RenameEvent::Failed {
+                error: Error::Conflict { destination, .. },
+                ..
+            } => self.progress_bar.suspend(|| {
+                println!(
+                    "{} {} {}",
+                    " WARNING ".black().on_yellow(),
+                    destination.display().blue(),
+                    "already exists. Skipping.".yellow()
+                )
+            }),
+            RenameEvent::Failed { error, .. } => self
+                .progress_bar
+                .suspend(|| eprintln!("{} {}", " ERROR ".black().on_red(), error.red())),
+        }
     }
-    let file_count = *file_count.lock().await;
-    Ok(file_count)
 }
 
 async fn print_summary(
@@ -228,13 +339,20 @@ async fn print_summary(
     renamed_folder: Arc<PathBuf>,
     cli: Arc<Args>,
 ) -> Result<()> {
+    if file_count.filtered > 0 {
+        println!(
+            "{} {}",
+            file_count.filtered.yellow(),
+            "Files outside of the date range were skipped.".yellow()
+        );
+    }
     if file_count.renamed == 0 {
         remove_dir(renamed_folder.as_ref()).await?;
-        if cli.extension {
+        if cli.extension || !cli.ext.is_empty() {
             eprintln!("No files selected");
             return Ok(());
         }
-        if cli.all {
+        if !renames_images_only(&cli) {
             eprintln!("No files found");
         } else {
             eprintln!(
@@ -250,7 +368,7 @@ async fn print_summary(
         eprintln!("Error calculating time{err}");
         std::time::Duration::default()
     });
-    if cli.all || cli.extension {
+    if !renames_images_only(&cli) {
         if file_count.renamed == file_count.total {
             println!(
                 "{}{}{}{}{}{}{:?}",
@@ -339,161 +457,12 @@ async fn get_extensions(files: &mut ReadDir) -> Result<Vec<String>> {
     }
 }
 
-async fn format_time(cli: Arc<Args>, file: &DirEntry) -> Result<String> {
-    let file_modified_at_system_time = file.metadata().await?.modified()?;
-    let file_modified_at_date_time: DateTime<Local> = file_modified_at_system_time.into();
-    let space_char = get_space_character(cli.clone());
-
-    if let Some(format) = &cli.format {
-        Ok(sanitize_filename::sanitize(
-            file_modified_at_date_time.format(format).to_string(),
-        ))
-    } else if cli.date {
-        Ok(file_modified_at_date_time.format("%Y-%m-%d").to_string())
-    } else if cli.twelve {
-        Ok(file_modified_at_date_time
-            .format(&format!("%Y-%m-%d{}%I-%M-%S-%p", space_char))
-            .to_string())
-    } else {
-        Ok(file_modified_at_date_time
-            .format(&format!("%Y-%m-%d{}%H-%M-%S", space_char))
-            .to_string())
-    }
-}
-
-async fn get_image_destination(
-    cli: Arc<Args>,
-    file: &DirEntry,
-    mut current_file: CurrentFile,
-    extension_selections: Vec<String>,
-    file_path: &Path,
-    file_count: &mut FileCount,
-    renamed_folder: Arc<PathBuf>,
-) -> Result<PathBuf> {
-    let Ok(file_name_with_extension) = file.file_name().into_string() else {
-        return Err(anyhow!(
-            "{}{}{:?}",
-            " ERROR ".black().on_red(),
-            " converting file name to string ".red(),
-            file_path.blue(),
-        ));
-    };
-    let mut dotfile = false;
-    let file_extension = if file_name_with_extension.starts_with('.') {
-        if let Some(extension) = file_name_with_extension.strip_prefix('.') {
-            dotfile = true;
-            extension
-        } else {
-            {
-                return Err(anyhow!(
-                    "{}{}{}",
-                    "Error getting file extension from ".red(),
-                    file_name_with_extension.blue(),
-                    ". File skipped".red()
-                ));
-            }
-        }
-    } else {
-        Path::new(&file_path)
-            .extension()
-            .and_then(OsStr::to_str)
-            .unwrap_or_default()
-    };
-    if cli.extension && !extension_selections.contains(&file_extension.to_owned()) {
-        bail!("");
-    }
-    if !cli.all
-        && !cli.extension
-        && !mime_guess::from_path(file_path)
-            .first()
-            .unwrap_or(Mime::from_str("unkown/unknown")?)
-            .to_string()
-            .starts_with("image")
-    {
-        bail!("")
-    }
-    file_count.total += 1;
-    if let Some(entered_prefix) = cli.name.as_deref() {
-        current_file.user_added_name = if cli.front {
-            get_filename_delimiter()
-                + &sanitize_filename::sanitize(String::from(entered_prefix).trim()).clone()
-        } else if cli.suffix {
-            get_filename_delimiter()
-                + &sanitize_filename::sanitize(String::from(entered_prefix).trim())
-        } else {
-            sanitize_filename::sanitize(String::from(entered_prefix).trim()).clone() + "-"
-        }
-    }
-    if !cli.no_name {
-        current_file.original_name = if cli.front && dotfile {
-            file_name_with_extension
-                .strip_suffix(&format!(".{file_extension}"))
-                .unwrap_or_default()
-                .to_string()
-        } else if cli.front {
-            get_filename_delimiter()
-                + file_name_with_extension
-                    .strip_suffix(&format!(".{file_extension}"))
-                    .unwrap_or_default()
-        } else if dotfile {
-            file_name_with_extension
-                .strip_suffix(&format!(".{file_extension}"))
-                .unwrap_or_default()
-                .to_string()
-        } else {
-            file_name_with_extension
-                .strip_suffix(&format!(".{file_extension}"))
-                .unwrap_or_default()
-                .to_string()
-                + &get_filename_delimiter()
-        }
-    }
-    let image_modified_at_time = format_time(cli.clone(), file).await?;
-    let image_destination = if cli.suffix {
-        if cli.front {
-            renamed_folder.join(format!(
-                "{}{}{}.{}",
-                image_modified_at_time,
-                current_file.original_name,
-                current_file.user_added_name.trim_end(),
-                file_extension
-            ))
-        } else {
-            renamed_folder.join(format!(
-                "{}{}{}.{}",
-                current_file.original_name,
-                image_modified_at_time,
-                current_file.user_added_name.trim_end(),
-                file_extension
-            ))
-        }
-    } else if cli.front {
-        renamed_folder.join(format!(
-            "{}{}{}.{}",
-            image_modified_at_time,
-            current_file.user_added_name,
-            current_file.original_name,
-            file_extension
-        ))
-    } else {
-        renamed_folder.join(format!(
-            "{}{}{}.{}",
-            current_file.user_added_name,
-            current_file.original_name,
-            image_modified_at_time,
-            file_extension
-        ))
-    };
-    Ok(image_destination)
-}
-
-fn get_space_character(cli: Arc<Args>) -> String {
-    if cli.space {
-        " ".to_owned()
-    } else {
-        "_".to_owned()
-    }
-}
-fn get_filename_delimiter() -> String {
-    "-".to_owned()
+fn renames_images_only(cli: &Args) -> bool {
+    !(cli.all
+        || cli.extension
+        || !cli.ext.is_empty()
+        || cli.videos
+        || cli.audio
+        || cli.documents
+        || cli.archives)
 }
@@ -0,0 +1,191 @@
+use std::{fs::File, path::Path, time::Duration};
+
+use crate::{
+    exif::Exif,
+    file_type::{sniff, FileType},
+    image_metadata::{png_exif, read_exif},
+    places::Place,
+    quicktime::{
+        find_atom, metadata_value, movie_duration, read_moov, track_dimensions, user_data_text,
+    },
+    video_metadata::video_info,
+};
+
+/// Placeholders for metadata in names, custom date formats and folder formats
+pub const PLACEHOLDERS: &[&str] = &[
+    "make", "model", "lens", "width", "height", "iso", "duration", "city", "country", "lat", "lon",
+];
+
+/// Placeholders that need the coordinates looked up in [`Places`](crate::places::Places)
+pub(crate) const PLACE_PLACEHOLDERS: &[&str] = &["city", "country"];
+
+/// Camera and picture details of a file, for the `{make}`, `{model}`, ... placeholders
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct MediaInfo {
+    pub(crate) make: Option<String>,
+    pub(crate) model: Option<String>,
+    pub(crate) lens: Option<String>,
+    pub(crate) width: Option<u32>,
+    pub(crate) height: Option<u32>,
+    pub(crate) iso: Option<u32>,
+    pub(crate) duration: Option<Duration>,
+    /// Latitude and longitude in degrees
+    pub(crate) gps: Option<(f64, f64)>,
+    /// The city closest to `gps`, only looked up when `{city}` or `{country}` is used
+    pub(crate) place: Option<Place>,
+}
+
+impl MediaInfo {
+    /// Read from the same metadata the dates come from, fields the file doesn't have stay empty
+    pub(crate) fn read(path: &Path, header: &[u8]) -> MediaInfo {
+        let info = match sniff(header) {
+            Some(FileType::Png) => png_info(path, header),
+            Some(
+                FileType::Mp4 | FileType::Mov | FileType::M4v | FileType::ThreeGp | FileType::M4a,
+            ) => quicktime_info(path),
+            Some(file_type @ (FileType::Mkv | FileType::Webm | FileType::Avi)) => {
+                video_info(path, file_type)
+            }
+            Some(_) => read_exif(path, header).map(MediaInfo::from),
+            None => None,
+        };
+        info.unwrap_or_default()
+    }
+
+    /// Replace the placeholders in `text`, `missing` stands in for values the file doesn't have
+    ///
+    /// Values are sanitized, so they never add folders or characters that aren't allowed in
+    /// file names. Unknown names in braces are left alone.
+    pub(crate) fn fill(&self, text: &str, missing: &str) -> String {
+        let mut filled = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find('{') {
+            filled.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let placeholder = rest
+                .find('}')
+                .map(|end| &rest[1..end])
+                .filter(|name| PLACEHOLDERS.contains(name));
+            let Some(name) = placeholder else {
+                filled.push('{');
+                rest = &rest[1..];
+                continue;
+            };
+            let value = self.value(name).unwrap_or_else(|| missing.to_owned());
+            filled.push_str(&sanitize_filename::sanitize(value.trim()));
+            rest = &rest[name.len() + 2..];
+        }
+        filled.push_str(rest);
+        filled
+    }
+
+    fn value(&self, name: &str) -> Option<String> {
+        match name {
+            "make" => self.make.clone(),
+            "model" => self.model.clone(),
+            "lens" => self.lens.clone(),
+            "width" => self.width.map(|width| width.to_string()),
+            "height" => self.height.map(|height| height.to_string()),
+            "iso" => self.iso.map(|iso| iso.to_string()),
+            "duration" => self.duration.map(format_duration),
+            "city" => self.place.as_ref().map(|place| place.city.clone()),
+            "country" => self.place.as_ref().map(|place| place.country.clone()),
+            "lat" => self.gps.map(|(latitude, _)| format!("{latitude:.4}")),
+            "lon" => self.gps.map(|(_, longitude)| format!("{longitude:.4}")),
+            _ => None,
+        }
+        .filter(|value| !value.trim().is_empty())
+    }
+}
+
+impl From<Exif> for MediaInfo {
+    fn from(exif: Exif) -> Self {
+        MediaInfo {
+            make: exif.make,
+            model: exif.model,
+            lens: exif.lens,
+            width: exif.width,
+            height: exif.height,
+            iso: exif.iso,
+            gps: exif.gps,
+            ..MediaInfo::default()
+        }
+    }
+}
+
+/// Whether a name or format uses any of these placeholders
+pub(crate) fn has_placeholders(text: &str, placeholders: &[&str]) -> bool {
+    placeholders
+        .iter()
+        .any(|name| text.contains(&format!("{{{name}}}")))
+}
+
+/// `45s`, `3m07s` or `1h02m03s`
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs_f64().round() as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}h{minutes:02}m{seconds:02}s")
+    } else if minutes > 0 {
+        format!("{minutes}m{seconds:02}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+/// The size from the `IHDR` chunk, which always comes first, and the camera from `eXIf`
+fn png_info(path: &Path, header: &[u8]) -> Option<MediaInfo> {
+    let number = |offset: usize| {
+        Some(u32::from_be_bytes(
+            header.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    Some(MediaInfo {
+        width: number(16),
+        height: number(20),
+        ..png_exif(path).map(MediaInfo::from).unwrap_or_default()
+    })
+}
+
+/// Duration, picture size, and the camera and location phones write into the metadata
+fn quicktime_info(path: &Path) -> Option<MediaInfo> {
+    let moov = read_moov(&mut File::open(path).ok()?)?;
+    let text = |key: &[u8]| {
+        let meta = find_atom(&moov, b"meta")?;
+        Some(String::from_utf8_lossy(metadata_value(meta, key)?).into_owned())
+    };
+    let (width, height) = track_dimensions(&moov).unzip();
+    let location = text(b"com.apple.quicktime.location.ISO6709").or_else(|| {
+        let xyz = user_data_text(&moov, b"\xA9xyz")?;
+        Some(String::from_utf8_lossy(xyz).into_owned())
+    });
+    Some(MediaInfo {
+        make: text(b"com.apple.quicktime.make"),
+        model: text(b"com.apple.quicktime.model"),
+        width,
+        height,
+        duration: movie_duration(&moov),
+        gps: location.as_deref().and_then(parse_iso6709),
+        ..MediaInfo::default()
+    })
+}
+
+/// Decimal degrees as phones write them, `+38.7223-009.1393+045.000/`
+fn parse_iso6709(text: &str) -> Option<(f64, f64)> {
+    let mut numbers = vec![];
+    let mut start = None;
+    for (index, character) in text.char_indices() {
+        if matches!(character, '+' | '-' | '/') {
+            if let Some(start) = start {
+                numbers.push(&text[start..index]);
+            }
+            start = (character != '/').then_some(index);
+        }
+    }
+    if let Some(start) = start {
+        numbers.push(&text[start..]);
+    }
+    let latitude: f64 = numbers.first()?.parse().ok()?;
+    let longitude: f64 = numbers.get(1)?.parse().ok()?;
+    (latitude.abs() <= 90.0 && longitude.abs() <= 180.0).then_some((latitude, longitude))
+}
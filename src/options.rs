@@ -0,0 +1,304 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, Local};
+
+use crate::{
+    date_source::{DateSource, DateSources},
+    file_type::Category,
+    progress::{Progress, ProgressListener},
+};
+
+/// How the date is written in new file names
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DateFormat {
+    /// `2024-07-17_14-30-00`
+    #[default]
+    DateTime,
+    /// `2024-07-17_02-30-00-PM`
+    TwelveHour,
+    /// `2024-07-17`
+    Date,
+    /// A custom strftime format ('%a %b %e %Y' = "Wed Jul 17 2024"), which can also
+    /// use the metadata [`PLACEHOLDERS`](crate::PLACEHOLDERS) (`'%Y-%m-%d {model}'`)
+    Custom(String),
+}
+
+/// Options for a [`Renamer`](crate::Renamer), built with chained setters
+///
+/// ```no_run
+/// use createdat::{Category, RenameOptions, Renamer};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let options = RenameOptions::new("DCIM")
+///     .target("renamed")
+///     .name("holiday")
+///     .categories([Category::Image, Category::Video]);
+/// let report = Renamer::new(options).run().await?;
+/// println!("{}/{} files renamed", report.count.renamed, report.count.total);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RenameOptions {
+    pub(crate) source: PathBuf,
+    pub(crate) target: PathBuf,
+    pub(crate) name: Option<String>,
+    pub(crate) front: bool,
+    pub(crate) suffix: bool,
+    pub(crate) keep_name: bool,
+    pub(crate) date_format: DateFormat,
+    pub(crate) folder_format: Option<String>,
+    pub(crate) missing_field: String,
+    pub(crate) places: Option<PathBuf>,
+    pub(crate) space: bool,
+    pub(crate) fix_extension: bool,
+    pub(crate) selected_extensions: Option<Vec<String>>,
+    pub(crate) extensions: Vec<String>,
+    pub(crate) exclude_extensions: Vec<String>,
+    pub(crate) include: Vec<String>,
+    pub(crate) exclude: Vec<String>,
+    pub(crate) regex: bool,
+    pub(crate) since: Option<DateTime<Local>>,
+    pub(crate) until: Option<DateTime<Local>>,
+    pub(crate) categories: Option<Vec<Category>>,
+    pub(crate) preview: bool,
+    pub(crate) sidecars: bool,
+    pub(crate) jobs: usize,
+    pub(crate) metadata_jobs: Option<usize>,
+    pub(crate) copy_jobs: Option<usize>,
+    pub(crate) date_sources: DateSources,
+    pub(crate) progress: Progress,
+}
+
+impl Default for RenameOptions {
+    fn default() -> Self {
+        RenameOptions {
+            source: PathBuf::from("."),
+            target: PathBuf::from("renamed"),
+            name: None,
+            front: false,
+            suffix: false,
+            keep_name: true,
+            date_format: DateFormat::default(),
+            folder_format: None,
+            missing_field: "unknown".to_owned(),
+            places: None,
+            space: false,
+            fix_extension: false,
+            selected_extensions: None,
+            extensions: vec![],
+            exclude_extensions: vec![],
+            include: vec![],
+            exclude: vec![],
+            regex: false,
+            since: None,
+            until: None,
+            categories: Some(vec![Category::Image]),
+            preview: false,
+            sidecars: true,
+            jobs: 15,
+            metadata_jobs: None,
+            copy_jobs: None,
+            date_sources: DateSources::builtin(),
+            progress: Progress::default(),
+        }
+    }
+}
+
+impl RenameOptions {
+    /// Rename the images in `source` into the `renamed` folder
+    pub fn new(source: impl Into<PathBuf>) -> Self {
+        RenameOptions::default().source(source)
+    }
+
+    /// Folder with the files to rename
+    pub fn source(mut self, source: impl Into<PathBuf>) -> Self {
+        self.source = source.into();
+        self
+    }
+
+    /// Folder the renamed copies are written to
+    pub fn target(mut self, target: impl Into<PathBuf>) -> Self {
+        self.target = target.into();
+        self
+    }
+
+    /// Custom name added to every file, before the original name by default, it can use
+    /// the metadata [`PLACEHOLDERS`](crate::PLACEHOLDERS) (`"{make}-{model}"`)
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Put the date in front of the file name
+    pub fn date_in_front(mut self, front: bool) -> Self {
+        self.front = front;
+        self
+    }
+
+    /// Put the custom name after the date
+    pub fn name_after_date(mut self, suffix: bool) -> Self {
+        self.suffix = suffix;
+        self
+    }
+
+    /// Keep the original file name in the new name
+    pub fn keep_original_name(mut self, keep_name: bool) -> Self {
+        self.keep_name = keep_name;
+        self
+    }
+
+    pub fn date_format(mut self, date_format: DateFormat) -> Self {
+        self.date_format = date_format;
+        self
+    }
+
+    /// Sort renamed files into folders named after their date, `/` separates
+    /// levels (`"%Y/%Y-%m-%d"` = `2024/2024-07-17`), metadata placeholders can be used too
+    /// (`"%Y/{model}"`)
+    pub fn folder_format(mut self, folder_format: impl Into<String>) -> Self {
+        self.folder_format = Some(folder_format.into());
+        self
+    }
+
+    /// Text used for metadata placeholders the file has no value for, `unknown` by default
+    pub fn missing_field(mut self, text: impl Into<String>) -> Self {
+        self.missing_field = text.into();
+        self
+    }
+
+    /// GeoNames file like `cities15000.txt` that `{city}` and `{country}` are looked up in,
+    /// instead of the bundled list of capitals and large cities
+    pub fn places_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.places = Some(path.into());
+        self
+    }
+
+    /// Use spaces instead of underscores between the date and time
+    pub fn spaces(mut self, space: bool) -> Self {
+        self.space = space;
+        self
+    }
+
+    /// Replace extensions that don't match the file contents
+    pub fn fix_extension(mut self, fix_extension: bool) -> Self {
+        self.fix_extension = fix_extension;
+        self
+    }
+
+    /// Only rename files with exactly these extensions, regardless of their category
+    pub fn selected_extensions(mut self, extensions: Vec<String>) -> Self {
+        self.selected_extensions = Some(extensions);
+        self.categories = None;
+        self
+    }
+
+    /// Only rename files with these extensions (case-insensitive, e.g. `tar.gz`),
+    /// regardless of their category
+    pub fn extensions<S: Into<String>>(mut self, extensions: impl IntoIterator<Item = S>) -> Self {
+        self.extensions = extensions.into_iter().map(Into::into).collect();
+        if !self.extensions.is_empty() {
+            self.categories = None;
+        }
+        self
+    }
+
+    /// Skip files with these extensions (case-insensitive)
+    pub fn exclude_extensions<S: Into<String>>(
+        mut self,
+        extensions: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.exclude_extensions = extensions.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Only rename files whose path relative to the source folder matches a pattern
+    pub fn include(mut self, pattern: impl Into<String>) -> Self {
+        self.include.push(pattern.into());
+        self
+    }
+
+    /// Skip files whose path relative to the source folder matches a pattern
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    /// Treat include and exclude patterns as regular expressions instead of globs
+    pub fn regex(mut self, regex: bool) -> Self {
+        self.regex = regex;
+        self
+    }
+
+    /// Only rename files dated on or after `since`
+    pub fn since(mut self, since: DateTime<Local>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    /// Only rename files dated on or before `until`
+    pub fn until(mut self, until: DateTime<Local>) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    /// Only rename files of these categories (images by default)
+    pub fn categories(mut self, categories: impl IntoIterator<Item = Category>) -> Self {
+        self.categories = Some(categories.into_iter().collect());
+        self
+    }
+
+    /// Rename all files, not just the selected categories
+    pub fn all_files(mut self) -> Self {
+        self.categories = None;
+        self
+    }
+
+    /// Work out the new names without copying any files
+    pub fn preview(mut self, preview: bool) -> Self {
+        self.preview = preview;
+        self
+    }
+
+    /// Give sidecars (`.xmp`, `.aae`, ...) the new name of the file they belong to, on by default
+    pub fn sidecars(mut self, sidecars: bool) -> Self {
+        self.sidecars = sidecars;
+        self
+    }
+
+    /// Number of files processed at once, `1` renames them one by one in name order
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Number of files read for their dates at once, defaults to [`RenameOptions::jobs`]
+    pub fn metadata_jobs(mut self, jobs: usize) -> Self {
+        self.metadata_jobs = Some(jobs.max(1));
+        self
+    }
+
+    /// Number of files copied at once, defaults to [`RenameOptions::jobs`]
+    pub fn copy_jobs(mut self, jobs: usize) -> Self {
+        self.copy_jobs = Some(jobs.max(1));
+        self
+    }
+
+    /// Add a source for file dates, consulted before the built-in ones
+    pub fn date_source(mut self, source: impl DateSource + 'static) -> Self {
+        self.date_sources.register(source);
+        self
+    }
+
+    /// Replace all date sources, including the built-in ones
+    pub fn date_sources(mut self, date_sources: DateSources) -> Self {
+        self.date_sources = date_sources;
+        self
+    }
+
+    /// Receive a [`RenameEvent`](crate::RenameEvent) for every file as it is processed
+    pub fn progress(mut self, listener: impl ProgressListener + 'static) -> Self {
+        self.progress = Progress::new(listener);
+        self
+    }
+}
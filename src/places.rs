@@ -0,0 +1,107 @@
+use std::{collections::HashMap, fs::read_to_string, path::Path};
+
+use crate::error::{Error, Result};
+
+/// Capitals and large cities: name, country code, latitude and longitude
+const BUNDLED_CITIES: &str = include_str!("../data/cities.tsv");
+
+/// ISO 3166 country codes and the names used for `{country}`
+const COUNTRIES: &str = include_str!("../data/countries.tsv");
+
+/// Coordinates further than this from every city get no `{city}` or `{country}`
+const MAX_DISTANCE_KM: f64 = 100.0;
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// A city that coordinates are matched against
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Place {
+    pub(crate) city: String,
+    pub(crate) country: String,
+    latitude: f64,
+    longitude: f64,
+}
+
+/// Cities for reverse geocoding without a network, the bundled list or a GeoNames file
+#[derive(Debug)]
+pub(crate) struct Places {
+    places: Vec<Place>,
+}
+
+impl Places {
+    /// The bundled list, or the cities in a GeoNames file like `cities15000.txt`
+    pub(crate) fn load(path: Option<&Path>) -> Result<Places> {
+        let countries: HashMap<&str, &str> = COUNTRIES
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .collect();
+        let place = |city: &str, country: &str, latitude: &str, longitude: &str| {
+            Some(Place {
+                city: city.to_owned(),
+                country: countries.get(country).unwrap_or(&country).to_string(),
+                latitude: latitude.parse().ok()?,
+                longitude: longitude.parse().ok()?,
+            })
+        };
+
+        let Some(path) = path else {
+            let places = BUNDLED_CITIES
+                .lines()
+                .filter_map(|line| {
+                    let fields: Vec<&str> = line.split('\t').collect();
+                    place(
+                        fields.first()?,
+                        fields.get(1)?,
+                        fields.get(2)?,
+                        fields.get(3)?,
+                    )
+                })
+                .collect();
+            return Ok(Places { places });
+        };
+        let content = read_to_string(path).map_err(Error::io(path))?;
+        // ID, name, ASCII name, alternate names, latitude, longitude, feature class and code,
+        // then the country code
+        let places: Vec<Place> = content
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split('\t').collect();
+                place(
+                    fields.get(1)?,
+                    fields.get(8)?,
+                    fields.get(4)?,
+                    fields.get(5)?,
+                )
+            })
+            .collect();
+        if places.is_empty() {
+            return Err(Error::Places {
+                path: path.to_path_buf(),
+            });
+        }
+        Ok(Places { places })
+    }
+
+    /// The closest city, unless they are all more than [`MAX_DISTANCE_KM`] away
+    pub(crate) fn nearest(&self, latitude: f64, longitude: f64) -> Option<&Place> {
+        self.places
+            .iter()
+            .map(|place| {
+                let distance = distance((latitude, longitude), (place.latitude, place.longitude));
+                (distance, place)
+            })
+            .filter(|&(distance, _)| distance <= MAX_DISTANCE_KM)
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, place)| place)
+    }
+}
+
+/// Great circle distance in kilometers between two points in degrees
+fn distance((latitude_a, longitude_a): (f64, f64), (latitude_b, longitude_b): (f64, f64)) -> f64 {
+    let (latitude_a, latitude_b) = (latitude_a.to_radians(), latitude_b.to_radians());
+    let latitude_delta = latitude_b - latitude_a;
+    let longitude_delta = (longitude_b - longitude_a).to_radians();
+    let haversine = (latitude_delta / 2.0).sin().powi(2)
+        + latitude_a.cos() * latitude_b.cos() * (longitude_delta / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * haversine.sqrt().asin()
+}
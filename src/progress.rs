@@ -0,0 +1,63 @@
+use std::{fmt, path::Path, sync::Arc};
+
+use crate::error::{Error, SkipReason};
+
+/// Progress of a [`Renamer`](crate::Renamer) run, emitted while files are processed
+#[derive(Debug)]
+pub enum RenameEvent<'a> {
+    /// A file was selected, with `size` bytes to copy to `destination`
+    Planned {
+        source: &'a Path,
+        destination: &'a Path,
+        size: u64,
+    },
+    /// A file was copied to `destination`
+    Copied {
+        source: &'a Path,
+        destination: &'a Path,
+        bytes: u64,
+    },
+    /// A file was not selected
+    Skipped { path: &'a Path, reason: SkipReason },
+    /// A file could not be renamed, including destinations that already exist
+    Failed { path: &'a Path, error: &'a Error },
+}
+
+/// Receives [`RenameEvent`]s, called from the tasks copying the files
+///
+/// Closures taking a `&RenameEvent` can be used as listeners.
+pub trait ProgressListener: Send + Sync {
+    fn on_event(&self, event: &RenameEvent<'_>);
+}
+
+impl<F> ProgressListener for F
+where
+    F: Fn(&RenameEvent<'_>) + Send + Sync,
+{
+    fn on_event(&self, event: &RenameEvent<'_>) {
+        self(event)
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Progress(Option<Arc<dyn ProgressListener>>);
+
+impl Progress {
+    pub(crate) fn new(listener: impl ProgressListener + 'static) -> Self {
+        Progress(Some(Arc::new(listener)))
+    }
+
+    pub(crate) fn emit(&self, event: RenameEvent<'_>) {
+        if let Some(listener) = &self.0 {
+            listener.on_event(&event);
+        }
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Progress")
+            .field(&self.0.as_ref().map(|_| "listener"))
+            .finish()
+    }
+}
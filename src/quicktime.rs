@@ -0,0 +1,171 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    time::Duration,
+};
+
+use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+
+/// Largest `moov` atom that is read into memory
+const MAX_MOOV_LENGTH: u64 = 64 * 1024 * 1024;
+
+/// The contents of the `moov` atom of a QuickTime or MP4 file, wherever it is in the file
+pub(crate) fn read_moov(file: &mut File) -> Option<Vec<u8>> {
+    let file_length = file.metadata().ok()?.len();
+    let mut position = 0u64;
+    // Sizes come from the file, so a 64 bit one can point past the end or wrap around
+    while position.checked_add(8)? <= file_length {
+        file.seek(SeekFrom::Start(position)).ok()?;
+        let mut header = [0; 16];
+        file.read_exact(&mut header[..8]).ok()?;
+        let mut header_length = 8;
+        let atom_length = match u32::from_be_bytes(header[..4].try_into().ok()?) {
+            0 => file_length - position,
+            1 => {
+                file.read_exact(&mut header[8..]).ok()?;
+                header_length = 16;
+                u64::from_be_bytes(header[8..].try_into().ok()?)
+            }
+            length => u64::from(length),
+        };
+        if atom_length < header_length {
+            return None;
+        }
+        if &header[4..8] == b"moov" {
+            let length = atom_length - header_length;
+            if length > MAX_MOOV_LENGTH {
+                return None;
+            }
+            let mut moov = vec![0; length as usize];
+            file.read_exact(&mut moov).ok()?;
+            return Some(moov);
+        }
+        position = position.checked_add(atom_length)?;
+    }
+    None
+}
+
+/// The value of a `mdta` key from the `keys` and `ilst` atoms in a `meta` atom
+pub(crate) fn metadata_value<'a>(meta: &'a [u8], key: &[u8]) -> Option<&'a [u8]> {
+    let meta = skip_meta_header(meta)?;
+    let keys = find_atom(meta, b"keys")?;
+    let key_count = u32::from_be_bytes(keys.get(4..8)?.try_into().ok()?);
+    let mut offset = 8;
+    let mut index = None;
+    for key_index in 1..=key_count {
+        let length = u32::from_be_bytes(keys.get(offset..offset + 4)?.try_into().ok()?) as usize;
+        if length < 8 {
+            return None;
+        }
+        if keys.get(offset + 8..offset + length)? == key {
+            index = Some(key_index);
+            break;
+        }
+        offset += length;
+    }
+    let index = index?.to_be_bytes();
+    item_value(find_atom(meta, b"ilst")?, &index)
+}
+
+/// The value of an iTunes style item like `©day` in `udta/meta/ilst`
+pub(crate) fn user_data_value<'a>(moov: &'a [u8], item: &[u8]) -> Option<&'a [u8]> {
+    let meta = skip_meta_header(find_atom(find_atom(moov, b"udta")?, b"meta")?)?;
+    item_value(find_atom(meta, b"ilst")?, item)
+}
+
+/// A QuickTime text item like `©xyz` directly in `udta`, after its length and language
+pub(crate) fn user_data_text<'a>(moov: &'a [u8], item: &[u8]) -> Option<&'a [u8]> {
+    let data = find_atom(find_atom(moov, b"udta")?, item)?;
+    let length = usize::from(u16::from_be_bytes(data.get(..2)?.try_into().ok()?));
+    data.get(4..4 + length)
+}
+
+/// QuickTime `meta` atoms have no version and flags, MP4 ones do
+fn skip_meta_header(meta: &[u8]) -> Option<&[u8]> {
+    match meta.get(4..8) {
+        Some(b"hdlr") => Some(meta),
+        _ => meta.get(4..),
+    }
+}
+
+fn item_value<'a>(ilst: &'a [u8], item: &[u8]) -> Option<&'a [u8]> {
+    // Type and locale come before the value
+    find_atom(find_atom(ilst, item)?, b"data")?.get(8..)
+}
+
+/// Contents of the first child atom with this type
+pub(crate) fn find_atom<'a>(atoms: &'a [u8], atom_type: &[u8]) -> Option<&'a [u8]> {
+    find_atoms(atoms, atom_type).next()
+}
+
+/// Contents of every child atom with this type, e.g. the `trak` atoms in `moov`
+fn find_atoms<'a>(atoms: &'a [u8], atom_type: &[u8]) -> impl Iterator<Item = &'a [u8]> {
+    let atom_type = atom_type.to_vec();
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        while offset + 8 <= atoms.len() {
+            let length = u32::from_be_bytes(atoms[offset..offset + 4].try_into().ok()?) as usize;
+            if length < 8 {
+                return None;
+            }
+            let atom = offset;
+            offset = offset.checked_add(length)?;
+            if atoms[atom + 4..atom + 8] == atom_type {
+                return atoms.get(atom + 8..atom + length);
+            }
+        }
+        None
+    })
+}
+
+/// When the movie was created from the `mvhd` atom, cameras that don't know the time leave it zero
+pub(crate) fn movie_creation_time(moov: &[u8]) -> Option<DateTime<Utc>> {
+    let mvhd = find_atom(moov, b"mvhd")?;
+    // Seconds since the start of 1904 in UTC, 64 bit in version 1
+    let seconds = if *mvhd.first()? == 1 {
+        u64::from_be_bytes(mvhd.get(4..12)?.try_into().ok()?)
+    } else {
+        u64::from(u32::from_be_bytes(mvhd.get(4..8)?.try_into().ok()?))
+    };
+    if seconds == 0 {
+        return None;
+    }
+    let epoch = Utc.with_ymd_and_hms(1904, 1, 1, 0, 0, 0).single()?;
+    epoch.checked_add_signed(TimeDelta::try_seconds(i64::try_from(seconds).ok()?)?)
+}
+
+/// Length of the movie from the `mvhd` atom
+pub(crate) fn movie_duration(moov: &[u8]) -> Option<Duration> {
+    let mvhd = find_atom(moov, b"mvhd")?;
+    // Version 1 has 64 bit times and duration
+    let (timescale, duration, unknown) = if *mvhd.first()? == 1 {
+        (
+            u32::from_be_bytes(mvhd.get(20..24)?.try_into().ok()?),
+            u64::from_be_bytes(mvhd.get(24..32)?.try_into().ok()?),
+            u64::MAX,
+        )
+    } else {
+        (
+            u32::from_be_bytes(mvhd.get(12..16)?.try_into().ok()?),
+            u64::from(u32::from_be_bytes(mvhd.get(16..20)?.try_into().ok()?)),
+            u64::from(u32::MAX),
+        )
+    };
+    // All bits set means the duration is unknown
+    if timescale == 0 || duration == unknown {
+        return None;
+    }
+    Duration::try_from_secs_f64(duration as f64 / f64::from(timescale)).ok()
+}
+
+/// Width and height of the first track with a picture, from its `tkhd` atom
+pub(crate) fn track_dimensions(moov: &[u8]) -> Option<(u32, u32)> {
+    find_atoms(moov, b"trak").find_map(|trak| {
+        let tkhd = find_atom(trak, b"tkhd")?;
+        // 16.16 fixed point numbers at the end of the atom
+        let size = tkhd.get(tkhd.len().checked_sub(8)?..)?;
+        let width = u32::from_be_bytes(size[..4].try_into().ok()?) >> 16;
+        let height = u32::from_be_bytes(size[4..].try_into().ok()?) >> 16;
+        (width > 0 && height > 0).then_some((width, height))
+    })
+}
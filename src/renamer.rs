@@ -0,0 +1,913 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fmt::Write,
+    fs::Metadata,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, FixedOffset, Local,
+};
+use tokio::{
+    fs::{self, create_dir_all, read_dir, ReadDir},
+    sync::{Mutex, Semaphore},
+    task::JoinSet,
+};
+
+use crate::{
+    date_range::DateRange,
+    error::{Error, Result, SkipReason},
+    file_type::{read_header, sniff, Category, FileType},
+    filter::FileFilter,
+    group::{
+        companion_suffix, group_files, live_photo_image, live_photo_video, sidecar_owner, Buckets,
+        FileGroup,
+    },
+    media_info::{has_placeholders, MediaInfo, PLACEHOLDERS, PLACE_PLACEHOLDERS},
+    options::{DateFormat, RenameOptions},
+    places::Places,
+    progress::RenameEvent,
+};
+
+struct CurrentFile {
+    user_added_name: String,
+    original_name: String,
+}
+
+/// Number of files selected, renamed and skipped during a run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileCount {
+    pub renamed: u32,
+    pub total: u32,
+    pub duplicate: u32,
+    pub filtered: u32,
+}
+
+/// What happened to a selected file
+#[derive(Debug)]
+pub enum FileStatus {
+    /// The file was copied to its destination
+    Renamed,
+    /// Preview run, the file would have been copied to its destination
+    Previewed,
+    /// Nothing was copied, [`Error::Conflict`] when the destination already exists
+    Failed(Error),
+}
+
+/// A selected file and its new path
+#[derive(Debug)]
+pub struct RenamedFile {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub status: FileStatus,
+}
+
+/// A file that was not selected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+/// A file that could not be renamed before its destination was known
+#[derive(Debug)]
+pub struct FailedFile {
+    pub path: PathBuf,
+    pub error: Error,
+}
+
+/// Result of a [`Renamer`] run
+#[derive(Debug, Default)]
+pub struct RenameReport {
+    pub count: FileCount,
+    pub files: Vec<RenamedFile>,
+    pub skipped: Vec<SkippedFile>,
+    pub failed: Vec<FailedFile>,
+}
+
+/// Where a file is copied to, unless it was left out
+enum Selection {
+    Destination(Destination),
+    Skipped(SkipReason),
+}
+
+/// New path of a file, `stem` is its new name without the extension
+struct Destination {
+    path: PathBuf,
+    stem: String,
+}
+
+/// Separate limits so slow copies don't hold up reading dates, and the other way around
+struct Limits {
+    metadata: Semaphore,
+    copy: Semaphore,
+    /// Files read and copied at the same time, enough to keep both limits busy
+    in_flight: usize,
+}
+
+impl Limits {
+    fn new(options: &RenameOptions) -> Self {
+        let metadata = options.metadata_jobs.unwrap_or(options.jobs);
+        let copy = options.copy_jobs.unwrap_or(options.jobs);
+        Limits {
+            metadata: Semaphore::new(metadata),
+            copy: Semaphore::new(copy),
+            in_flight: metadata + copy,
+        }
+    }
+}
+
+/// Copies files into the target folder with the date they were created in their names
+pub struct Renamer {
+    options: Arc<RenameOptions>,
+}
+
+impl Renamer {
+    pub fn new(options: RenameOptions) -> Self {
+        Renamer {
+            options: Arc::new(options),
+        }
+    }
+
+    pub fn options(&self) -> &RenameOptions {
+        &self.options
+    }
+
+    /// Rename the files in the source folder
+    pub async fn run(&self) -> Result<RenameReport> {
+        let files = Files::Folder(self.options.source.clone());
+        // Also makes sure the folder can be read before the target is made
+        let mut entries = files.entries().await?;
+        if self.options.jobs == 1 {
+            // One file at a time goes in name order, which needs the whole listing
+            let mut paths = vec![];
+            while let Some(path) = entries.next().await {
+                paths.push(path);
+            }
+            paths.sort();
+            return self.copy_files(Files::Paths(paths)).await;
+        }
+        self.copy_files(files).await
+    }
+
+    /// Rename only these files, e.g. ones that just arrived in the source folder
+    pub async fn rename_paths<P: Into<PathBuf>>(
+        &self,
+        paths: impl IntoIterator<Item = P>,
+    ) -> Result<RenameReport> {
+        let paths = paths.into_iter().map(Into::into).collect();
+        self.copy_files(Files::Paths(paths)).await
+    }
+
+    async fn copy_files(&self, files: Files) -> Result<RenameReport> {
+        check_formats(&self.options)?;
+        let file_filter = FileFilter::new(&self.options)?;
+        let places = if uses_placeholders(&self.options, PLACE_PLACEHOLDERS) {
+            Some(Arc::new(Places::load(self.options.places.as_deref())?))
+        } else {
+            None
+        };
+
+        let mut buckets = Buckets::default();
+        let mut entries = files.entries().await?;
+        while let Some(path) = entries.next().await {
+            buckets.count(&path);
+        }
+
+        if !self.options.preview {
+            create_dir_all(&self.options.target)
+                .await
+                .map_err(Error::io(&self.options.target))?;
+        }
+
+        copy_files(files, buckets, self.options.clone(), file_filter, places).await
+    }
+}
+
+/// Files to rename, listed from the source folder or given up front
+enum Files {
+    Folder(PathBuf),
+    Paths(Vec<PathBuf>),
+}
+
+impl Files {
+    /// The folder is listed again every time
+    async fn entries(&self) -> Result<Entries> {
+        Ok(match self {
+            Files::Folder(folder) => {
+                Entries::Folder(read_dir(folder).await.map_err(Error::io(folder))?)
+            }
+            Files::Paths(paths) => Entries::Paths(paths.clone().into_iter()),
+        })
+    }
+}
+
+enum Entries {
+    Folder(ReadDir),
+    Paths(std::vec::IntoIter<PathBuf>),
+}
+
+impl Entries {
+    async fn next(&mut self) -> Option<PathBuf> {
+        match self {
+            Entries::Folder(entries) => entries
+                .next_entry()
+                .await
+                .ok()
+                .flatten()
+                .map(|file| file.path()),
+            Entries::Paths(paths) => paths.next(),
+        }
+    }
+}
+
+/// What the tasks of a run share
+struct Run {
+    options: Arc<RenameOptions>,
+    file_filter: FileFilter,
+    places: Option<Arc<Places>>,
+    limits: Limits,
+    report: Mutex<RenameReport>,
+    live_photos: LivePhotos,
+}
+
+/// Live Photo images and videos with different names, paired by their content identifier
+///
+/// Videos without an image of the same name wait until everything else is renamed, then
+/// follow the new name of the image with their identifier.
+#[derive(Default)]
+struct LivePhotos {
+    /// Only when both images and videos are renamed
+    enabled: bool,
+    /// Images are only read when a video without an image of the same name was listed
+    read_images: bool,
+    images: Mutex<HashMap<(PathBuf, String), Destination>>,
+    videos: Mutex<Vec<((PathBuf, String), FileGroup)>>,
+}
+
+/// Rename the files listed again after `buckets` counted them
+async fn copy_files(
+    files: Files,
+    mut buckets: Buckets,
+    options: Arc<RenameOptions>,
+    file_filter: FileFilter,
+    places: Option<Arc<Places>>,
+) -> Result<RenameReport> {
+    let live_photos = options.categories.as_ref().is_none_or(|categories| {
+        categories.contains(&Category::Image) && categories.contains(&Category::Video)
+    });
+    let run = Arc::new(Run {
+        limits: Limits::new(&options),
+        options,
+        file_filter,
+        places,
+        report: Mutex::new(RenameReport::default()),
+        live_photos: LivePhotos {
+            enabled: live_photos,
+            read_images: live_photos && buckets.lone_videos(),
+            ..LivePhotos::default()
+        },
+    });
+
+    // Entries are read from the folder as tasks finish and only buckets still waiting for
+    // files with the same name are held, what grows with the folder are the counts from
+    // the first listing and the report
+    let mut tasks = JoinSet::new();
+    let mut entries = files.entries().await?;
+    while let Some(path) = entries.next().await {
+        // Folders made since the first listing, like the target, weren't there to rename
+        if !buckets.counted(&path) && fs::metadata(&path).await.is_ok_and(|path| path.is_dir()) {
+            continue;
+        }
+        if let Some(bucket) = buckets.add(path) {
+            start_bucket(bucket, &run, &mut tasks).await?;
+        }
+    }
+    for bucket in buckets.rest() {
+        start_bucket(bucket, &run, &mut tasks).await?;
+    }
+    while let Some(task) = tasks.join_next().await {
+        task??;
+    }
+
+    // Live Photo videos whose image had another name follow it now
+    let videos = std::mem::take(&mut *run.live_photos.videos.lock().await);
+    let images = std::mem::take(&mut *run.live_photos.images.lock().await);
+    for (identifier, group) in videos {
+        let lead = images.get(&identifier);
+        let places = run.places.as_ref();
+        let (options, filter, limits, report) =
+            (&run.options, &run.file_filter, &run.limits, &run.report);
+        rename_group(group, lead, options, filter, places, limits, report).await?;
+    }
+    let report = std::mem::take(&mut *run.report.lock().await);
+    Ok(report)
+}
+
+/// Rename a bucket right away with one job, otherwise once fewer than the limit are running
+async fn start_bucket(
+    bucket: Vec<PathBuf>,
+    run: &Arc<Run>,
+    tasks: &mut JoinSet<Result<()>>,
+) -> Result<()> {
+    if run.options.jobs == 1 {
+        return rename_bucket(bucket, run).await;
+    }
+    if tasks.len() >= run.limits.in_flight {
+        if let Some(task) = tasks.join_next().await {
+            task??;
+        }
+    }
+    let run = run.clone();
+    tasks.spawn(async move { rename_bucket(bucket, &run).await });
+    Ok(())
+}
+
+/// Group the files of a bucket and rename each group, lone Live Photo videos wait for the end
+async fn rename_bucket(bucket: Vec<PathBuf>, run: &Run) -> Result<()> {
+    let sidecars = run.options.sidecars;
+    let date_sources = run.options.date_sources.clone();
+    let LivePhotos {
+        enabled,
+        read_images,
+        ..
+    } = run.live_photos;
+    // Pairs are found by reading the files, which counts against the metadata jobs
+    let metadata_permit = run.limits.metadata.acquire().await;
+    let groups = tokio::task::spawn_blocking(move || {
+        group_files(bucket, sidecars, enabled, &date_sources)
+            .into_iter()
+            .map(|group| {
+                let video = enabled.then(|| live_photo_video(&group)).flatten();
+                let image = (video.is_none() && read_images)
+                    .then(|| live_photo_image(&group))
+                    .flatten();
+                (group, video, image)
+            })
+            .collect::<Vec<_>>()
+    })
+    .await?;
+    drop(metadata_permit);
+
+    let places = run.places.as_ref();
+    let (options, filter, limits, report) =
+        (&run.options, &run.file_filter, &run.limits, &run.report);
+    for (group, video, image) in groups {
+        if let Some(identifier) = video {
+            run.live_photos
+                .videos
+                .lock()
+                .await
+                .push((identifier, group));
+            continue;
+        }
+        let lead = rename_group(group, None, options, filter, places, limits, report).await?;
+        if let Some((identifier, lead)) = image.zip(lead) {
+            run.live_photos.images.lock().await.insert(identifier, lead);
+        }
+    }
+    Ok(())
+}
+
+/// Rename the files of a group under the new name of the first one that is selected, or of
+/// `lead` from another group, its sidecars follow it
+///
+/// Returns the destination of the first file that was renamed.
+async fn rename_group(
+    group: FileGroup,
+    lead: Option<&Destination>,
+    options: &Arc<RenameOptions>,
+    file_filter: &FileFilter,
+    places: Option<&Arc<Places>>,
+    limits: &Limits,
+    report: &Mutex<RenameReport>,
+) -> Result<Option<Destination>> {
+    let mut renamed: Vec<(PathBuf, Destination)> = vec![];
+    for file in &group.files {
+        let lead = renamed.first().map(|(_, destination)| destination).or(lead);
+        let Some((destination, size)) =
+            select_file(file, lead, options, file_filter, places, limits, report).await
+        else {
+            continue;
+        };
+        copy_file(
+            file.clone(),
+            destination.path.clone(),
+            size,
+            options,
+            limits,
+            report,
+        )
+        .await;
+        renamed.push((file.clone(), destination));
+    }
+    let Some((primary, destination)) = renamed.first() else {
+        for sidecar in group.sidecars {
+            skip_file(options, report, sidecar, SkipReason::Companion).await;
+        }
+        return Ok(None);
+    };
+
+    for sidecar in group.sidecars {
+        let relative_path = sidecar.strip_prefix(&options.source).unwrap_or(&sidecar);
+        let file_name = sidecar
+            .file_name()
+            .and_then(OsStr::to_str)
+            .unwrap_or_default();
+        if let Err(reason) = file_filter.check_excluded(relative_path, file_name) {
+            skip_file(options, report, sidecar, reason).await;
+            continue;
+        }
+        let metadata = match fs::symlink_metadata(&sidecar).await {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                let error = Error::io(&sidecar)(err);
+                fail_file(options, report, sidecar, error).await;
+                continue;
+            }
+        };
+        // `IMG_1234.CR2.xmp` follows the new name of the CR2 file, and only when it was renamed
+        let sidecar_destination = match sidecar_owner(&sidecar, &group.files) {
+            Some(owner) => {
+                let Some((_, owner_destination)) = renamed.iter().find(|(file, _)| file == owner)
+                else {
+                    skip_file(options, report, sidecar, SkipReason::Companion).await;
+                    continue;
+                };
+                let mut name = owner_destination.path.as_os_str().to_owned();
+                if let Some(extension) = sidecar.extension() {
+                    name.push(".");
+                    name.push(extension);
+                }
+                PathBuf::from(name)
+            }
+            None => destination.path.with_file_name(format!(
+                "{}{}",
+                destination.stem,
+                companion_suffix(primary, &sidecar)
+            )),
+        };
+        report.lock().await.count.total += 1;
+        copy_file(
+            sidecar,
+            sidecar_destination,
+            metadata.len(),
+            options,
+            limits,
+            report,
+        )
+        .await;
+    }
+    Ok(renamed
+        .into_iter()
+        .next()
+        .map(|(_, destination)| destination))
+}
+
+/// The destination and size of a selected file, skipped and failed files are reported
+///
+/// Files paired with an earlier file in their group take the new name of that `lead`.
+async fn select_file(
+    file_path: &Path,
+    lead: Option<&Destination>,
+    options: &Arc<RenameOptions>,
+    file_filter: &FileFilter,
+    places: Option<&Arc<Places>>,
+    limits: &Limits,
+    report: &Mutex<RenameReport>,
+) -> Option<(Destination, u64)> {
+    let current_file = CurrentFile {
+        user_added_name: String::new(),
+        original_name: String::new(),
+    };
+    let _metadata_permit = limits.metadata.acquire().await;
+
+    let metadata = match fs::symlink_metadata(file_path).await {
+        Ok(metadata) => metadata,
+        Err(err) => {
+            let error = Error::io(file_path)(err);
+            fail_file(options, report, file_path.to_path_buf(), error).await;
+            return None;
+        }
+    };
+    if metadata.is_dir() {
+        skip_file(
+            options,
+            report,
+            file_path.to_path_buf(),
+            SkipReason::Directory,
+        )
+        .await;
+        return None;
+    }
+
+    let selection = get_image_destination(
+        options.clone(),
+        file_path,
+        &metadata,
+        current_file,
+        file_filter,
+        places,
+        lead,
+    )
+    .await;
+    match selection {
+        Ok(Selection::Destination(destination)) => {
+            report.lock().await.count.total += 1;
+            Some((destination, metadata.len()))
+        }
+        Ok(Selection::Skipped(reason)) => {
+            skip_file(options, report, file_path.to_path_buf(), reason).await;
+            None
+        }
+        Err(error) => {
+            fail_file(options, report, file_path.to_path_buf(), error).await;
+            None
+        }
+    }
+}
+
+async fn skip_file(
+    options: &RenameOptions,
+    report: &Mutex<RenameReport>,
+    path: PathBuf,
+    reason: SkipReason,
+) {
+    options.progress.emit(RenameEvent::Skipped {
+        path: &path,
+        reason,
+    });
+    let mut report = report.lock().await;
+    if reason == SkipReason::DateRange {
+        report.count.filtered += 1;
+    }
+    report.skipped.push(SkippedFile { path, reason });
+}
+
+async fn fail_file(
+    options: &RenameOptions,
+    report: &Mutex<RenameReport>,
+    path: PathBuf,
+    error: Error,
+) {
+    options.progress.emit(RenameEvent::Failed {
+        path: &path,
+        error: &error,
+    });
+    report.lock().await.failed.push(FailedFile { path, error });
+}
+
+async fn copy_file(
+    source: PathBuf,
+    destination: PathBuf,
+    size: u64,
+    options: &RenameOptions,
+    limits: &Limits,
+    report: &Mutex<RenameReport>,
+) {
+    let progress = &options.progress;
+    let mut renamed_file = RenamedFile {
+        source,
+        destination,
+        status: FileStatus::Previewed,
+    };
+    progress.emit(RenameEvent::Planned {
+        source: &renamed_file.source,
+        destination: &renamed_file.destination,
+        size,
+    });
+    if options.preview {
+        report.lock().await.files.push(renamed_file);
+        return;
+    }
+
+    if Path::new(&renamed_file.destination).exists() {
+        let error = Error::Conflict {
+            path: renamed_file.source.clone(),
+            destination: renamed_file.destination.clone(),
+        };
+        progress.emit(RenameEvent::Failed {
+            path: &renamed_file.source,
+            error: &error,
+        });
+        renamed_file.status = FileStatus::Failed(error);
+        let mut report = report.lock().await;
+        report.count.duplicate += 1;
+        report.files.push(renamed_file);
+        return;
+    }
+
+    let _copy_permit = limits.copy.acquire().await;
+    let max_retries: u8 = 3;
+    let retry_delay_ms: u64 = 100;
+    let mut attempt: u8 = 0;
+
+    loop {
+        let copy_result = async {
+            if let (Some(_), Some(folder)) =
+                (&options.folder_format, renamed_file.destination.parent())
+            {
+                create_dir_all(folder).await?;
+            }
+            fs::copy(&renamed_file.source, &renamed_file.destination).await
+        }
+        .await;
+        match copy_result {
+            Ok(bytes) => {
+                progress.emit(RenameEvent::Copied {
+                    source: &renamed_file.source,
+                    destination: &renamed_file.destination,
+                    bytes,
+                });
+                renamed_file.status = FileStatus::Renamed;
+                let mut report = report.lock().await;
+                report.count.renamed += 1;
+                report.files.push(renamed_file);
+                break;
+            }
+            Err(err) => {
+                attempt += 1;
+                if attempt >= max_retries {
+                    let error = Error::io(&renamed_file.destination)(err);
+                    progress.emit(RenameEvent::Failed {
+                        path: &renamed_file.source,
+                        error: &error,
+                    });
+                    renamed_file.status = FileStatus::Failed(error);
+                    report.lock().await.files.push(renamed_file);
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(retry_delay_ms)).await;
+            }
+        }
+    }
+}
+
+async fn get_file_date(
+    options: Arc<RenameOptions>,
+    file_path: &Path,
+    metadata: &Metadata,
+    header: Vec<u8>,
+) -> Result<DateTime<FixedOffset>> {
+    let file_path = file_path.to_path_buf();
+    let metadata = metadata.clone();
+    tokio::task::spawn_blocking(move || {
+        match options
+            .date_sources
+            .find_date(&file_path, &metadata, &header)
+        {
+            Some(found) => Ok(found.date),
+            None => Err(Error::UnsupportedMetadata { path: file_path }),
+        }
+    })
+    .await?
+}
+
+fn format_time(
+    options: Arc<RenameOptions>,
+    file_modified_at_date_time: DateTime<FixedOffset>,
+    media_info: &MediaInfo,
+) -> Result<String> {
+    let space_char = get_space_character(options.clone());
+
+    Ok(match &options.date_format {
+        DateFormat::Custom(format) => media_info.fill(
+            &sanitize_filename::sanitize(format_date(file_modified_at_date_time, format)?),
+            &options.missing_field,
+        ),
+        DateFormat::Date => file_modified_at_date_time.format("%Y-%m-%d").to_string(),
+        DateFormat::TwelveHour => file_modified_at_date_time
+            .format(&format!("%Y-%m-%d{}%I-%M-%S-%p", space_char))
+            .to_string(),
+        DateFormat::DateTime => file_modified_at_date_time
+            .format(&format!("%Y-%m-%d{}%H-%M-%S", space_char))
+            .to_string(),
+    })
+}
+
+/// Each folder in the formatted date is sanitized, so only `/` adds levels
+fn format_folder(
+    folder_format: &str,
+    file_date: DateTime<FixedOffset>,
+    media_info: &MediaInfo,
+    missing: &str,
+) -> Result<PathBuf> {
+    Ok(format_date(file_date, folder_format)?
+        .split('/')
+        .map(|folder| media_info.fill(&sanitize_filename::sanitize(folder), missing))
+        .filter(|folder| !folder.is_empty())
+        .collect())
+}
+
+/// A date in a user's format, which [`check_formats`] has checked before the run
+fn format_date(date: DateTime<FixedOffset>, format: &str) -> Result<String> {
+    let mut formatted = String::new();
+    write!(formatted, "{}", date.format(format)).map_err(|_| Error::Format(format.to_owned()))?;
+    Ok(formatted)
+}
+
+/// Fail before copying anything when the custom date or folder format can't be formatted
+fn check_formats(options: &RenameOptions) -> Result<()> {
+    let custom_format = match &options.date_format {
+        DateFormat::Custom(format) => Some(format),
+        _ => None,
+    };
+    let invalid = [custom_format, options.folder_format.as_ref()]
+        .into_iter()
+        .flatten()
+        .find(|format| StrftimeItems::new(format).any(|item| matches!(item, Item::Error)));
+    match invalid {
+        Some(format) => Err(Error::Format(format.clone())),
+        None => Ok(()),
+    }
+}
+
+/// Metadata is only read, and places only loaded, for names and folders that use them
+fn uses_placeholders(options: &RenameOptions, placeholders: &[&str]) -> bool {
+    let custom_format = match &options.date_format {
+        DateFormat::Custom(format) => Some(format),
+        _ => None,
+    };
+    [
+        options.name.as_ref(),
+        custom_format,
+        options.folder_format.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .any(|template| has_placeholders(template, placeholders))
+}
+
+async fn get_image_destination(
+    options: Arc<RenameOptions>,
+    file_path: &Path,
+    metadata: &Metadata,
+    mut current_file: CurrentFile,
+    file_filter: &FileFilter,
+    places: Option<&Arc<Places>>,
+    lead: Option<&Destination>,
+) -> Result<Selection> {
+    let Some(file_name_with_extension) = file_path.file_name().and_then(OsStr::to_str) else {
+        return Err(Error::UnreadableName {
+            path: file_path.to_path_buf(),
+        });
+    };
+    let mut dotfile = false;
+    let file_extension = if let Some(extension) = file_name_with_extension.strip_prefix('.') {
+        dotfile = true;
+        extension
+    } else {
+        file_path
+            .extension()
+            .and_then(OsStr::to_str)
+            .unwrap_or_default()
+    };
+    let relative_path = file_path.strip_prefix(&options.source).unwrap_or(file_path);
+    if let Err(reason) = file_filter.check(relative_path, file_name_with_extension, file_extension)
+    {
+        return Ok(Selection::Skipped(reason));
+    }
+    let header = read_header(file_path).await.unwrap_or_default();
+    let file_type = sniff(&header);
+    if let Err(reason) =
+        file_filter.check_category(Category::from_mime(&get_file_mime(file_path, file_type)))
+    {
+        return Ok(Selection::Skipped(reason));
+    }
+    let name_extension = match file_filter.matched_extension(file_name_with_extension) {
+        Some(extension) if !dotfile => extension,
+        _ => file_extension,
+    };
+    let file_stem = if name_extension.is_empty() {
+        file_name_with_extension
+    } else {
+        file_name_with_extension
+            .strip_suffix(&format!(".{name_extension}"))
+            .unwrap_or_default()
+    };
+    let output_extension = match file_type {
+        Some(file_type) if options.fix_extension && !file_type.has_extension(file_extension) => {
+            format!(".{}", file_type.extensions()[0])
+        }
+        _ if name_extension.is_empty() => String::new(),
+        _ => format!(".{name_extension}"),
+    };
+    if let Some(lead) = lead {
+        return Ok(Selection::Destination(Destination {
+            path: lead
+                .path
+                .with_file_name(format!("{}{output_extension}", lead.stem)),
+            stem: lead.stem.clone(),
+        }));
+    }
+    let file_date = get_file_date(options.clone(), file_path, metadata, header.clone()).await?;
+    let date_range = DateRange {
+        since: options.since,
+        until: options.until,
+    };
+    if !date_range.contains(&file_date.with_timezone(&Local)) {
+        return Ok(Selection::Skipped(SkipReason::DateRange));
+    }
+    let media_info = if uses_placeholders(&options, PLACEHOLDERS) {
+        let file_path = file_path.to_path_buf();
+        let places = places.cloned();
+        tokio::task::spawn_blocking(move || {
+            let mut media_info = MediaInfo::read(&file_path, &header);
+            if let (Some(places), Some((latitude, longitude))) = (places, media_info.gps) {
+                media_info.place = places.nearest(latitude, longitude).cloned();
+            }
+            media_info
+        })
+        .await?
+    } else {
+        MediaInfo::default()
+    };
+    if let Some(entered_prefix) = options.name.as_deref() {
+        let entered_prefix = media_info.fill(
+            &sanitize_filename::sanitize(String::from(entered_prefix).trim()),
+            &options.missing_field,
+        );
+        current_file.user_added_name = if options.front || options.suffix {
+            get_filename_delimiter() + &entered_prefix
+        } else {
+            entered_prefix + "-"
+        }
+    }
+    if options.keep_name {
+        current_file.original_name = if options.front && dotfile {
+            file_stem.to_string()
+        } else if options.front {
+            get_filename_delimiter() + file_stem
+        } else if dotfile {
+            file_stem.to_string()
+        } else {
+            file_stem.to_string() + &get_filename_delimiter()
+        }
+    }
+    let image_modified_at_time = format_time(options.clone(), file_date, &media_info)?;
+    let renamed_folder = &match &options.folder_format {
+        Some(folder_format) => options.target.join(format_folder(
+            folder_format,
+            file_date,
+            &media_info,
+            &options.missing_field,
+        )?),
+        None => options.target.clone(),
+    };
+    let stem = if options.suffix {
+        if options.front {
+            format!(
+                "{}{}{}",
+                image_modified_at_time,
+                current_file.original_name,
+                current_file.user_added_name.trim_end()
+            )
+        } else {
+            format!(
+                "{}{}{}",
+                current_file.original_name,
+                image_modified_at_time,
+                current_file.user_added_name.trim_end()
+            )
+        }
+    } else if options.front {
+        format!(
+            "{}{}{}",
+            image_modified_at_time, current_file.user_added_name, current_file.original_name
+        )
+    } else {
+        format!(
+            "{}{}{}",
+            current_file.user_added_name, current_file.original_name, image_modified_at_time
+        )
+    };
+    Ok(Selection::Destination(Destination {
+        path: renamed_folder.join(format!("{stem}{output_extension}")),
+        stem,
+    }))
+}
+
+fn get_file_mime(file_path: &Path, file_type: Option<FileType>) -> String {
+    match file_type {
+        Some(file_type) => file_type.mime().to_owned(),
+        None => mime_guess::from_path(file_path)
+            .first()
+            .map(|mime| mime.to_string())
+            .unwrap_or_else(|| "unknown/unknown".to_owned()),
+    }
+}
+
+fn get_space_character(options: Arc<RenameOptions>) -> String {
+    if options.space {
+        " ".to_owned()
+    } else {
+        "_".to_owned()
+    }
+}
+fn get_filename_delimiter() -> String {
+    "-".to_owned()
+}
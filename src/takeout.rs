@@ -0,0 +1,96 @@
+use std::{fs, fs::Metadata, path::Path};
+
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+
+use crate::date_source::{Confidence, DateSource, FoundDate};
+
+/// Takeout cuts JSON names to this many characters before adding `.json`
+const MAX_NAME_LENGTH: usize = 46;
+
+const SUPPLEMENTAL: &str = ".supplemental-metadata";
+
+/// Suffixes Google Photos adds to edited copies, which share the JSON of the original
+const EDITED_SUFFIXES: &[&str] = &[
+    "-edited",
+    "-bearbeitet",
+    "-modifié",
+    "-editado",
+    "-modificato",
+];
+
+/// The date from the JSON file Google Takeout exports next to every photo
+///
+/// Google Photos strips the metadata from exported files and writes it into
+/// `photo.jpg.json`, or `photo.jpg.supplemental-metadata.json` in newer exports.
+/// Long names are cut short and duplicates like `photo(1).jpg` use `photo.jpg(1).json`.
+pub struct GoogleTakeout;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TakeoutMetadata {
+    photo_taken_time: TakeoutTime,
+}
+
+#[derive(Deserialize)]
+struct TakeoutTime {
+    /// Seconds since the Unix epoch, as a string
+    timestamp: String,
+}
+
+impl DateSource for GoogleTakeout {
+    fn name(&self) -> &str {
+        "google takeout"
+    }
+
+    fn find_date(&self, path: &Path, _metadata: &Metadata, _header: &[u8]) -> Option<FoundDate> {
+        let folder = path.parent()?;
+        let file_name = path.file_name()?.to_str()?;
+        let json = json_names(file_name)
+            .into_iter()
+            .find_map(|name| fs::read(folder.join(name)).ok())?;
+        let metadata: TakeoutMetadata = serde_json::from_slice(&json).ok()?;
+        let timestamp = metadata.photo_taken_time.timestamp.trim().parse().ok()?;
+        let date: DateTime<Local> = DateTime::from_timestamp(timestamp, 0)?.into();
+        Some(FoundDate::new(date.fixed_offset(), Confidence::High))
+    }
+}
+
+/// Names the JSON file of `file_name` can have, most likely first
+fn json_names(file_name: &str) -> Vec<String> {
+    let (stem, extension) = match file_name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{extension}")),
+        _ => (file_name, String::new()),
+    };
+    // `photo(1).jpg` uses the JSON name of `photo.jpg` with `(1)` at the end
+    let (stem, duplicate) = match stem.rsplit_once('(') {
+        Some((original, number))
+            if number
+                .strip_suffix(')')
+                .is_some_and(|number| number.parse::<u32>().is_ok()) =>
+        {
+            (original, &stem[original.len()..])
+        }
+        _ => (stem, ""),
+    };
+    let mut originals = vec![format!("{stem}{extension}")];
+    for suffix in EDITED_SUFFIXES {
+        if let Some(original) = stem.strip_suffix(suffix) {
+            originals.push(format!("{original}{extension}"));
+        }
+    }
+
+    let mut names = vec![];
+    for original in &originals {
+        for supplemental in ["", SUPPLEMENTAL] {
+            let name: String = format!("{original}{supplemental}")
+                .chars()
+                .take(MAX_NAME_LENGTH)
+                .collect();
+            names.push(format!("{name}{duplicate}.json"));
+        }
+    }
+    names.push(format!("{stem}{duplicate}.json"));
+    names.dedup();
+    names
+}
@@ -0,0 +1,361 @@
+use std::{
+    fs::{File, Metadata},
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+    time::Duration,
+};
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeDelta, TimeZone, Utc};
+
+use crate::{
+    date_source::{Confidence, DateSource, FoundDate},
+    exif,
+    file_type::{sniff, FileType},
+    media_info::MediaInfo,
+    quicktime::{find_atom, metadata_value, movie_creation_time, read_moov, user_data_value},
+    xmp,
+};
+
+/// Bytes of a Matroska file searched for the segment info, which comes before the clusters
+const MATROSKA_LENGTH: u64 = 1024 * 1024;
+
+/// Largest AVI header or info list that is read into memory
+const MAX_LIST_LENGTH: u32 = 16 * 1024 * 1024;
+
+/// Deepest list inside the `hdrl` or `INFO` list that is read, AVI files nest `strl` in `hdrl`
+/// and `odml` lists one level deep
+const MAX_LIST_DEPTH: usize = 4;
+
+const SEGMENT: u32 = 0x1853_8067;
+const INFO: u32 = 0x1549_A966;
+const DATE_UTC: u32 = 0x4461;
+const TIMECODE_SCALE: u32 = 0x2A_D7B1;
+const DURATION: u32 = 0x4489;
+const TRACKS: u32 = 0x1654_AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const VIDEO: u32 = 0xE0;
+const PIXEL_WIDTH: u32 = 0xB0;
+const PIXEL_HEIGHT: u32 = 0xBA;
+const CLUSTER: u32 = 0x1F43_B675;
+
+/// The recording date of MP4, QuickTime, Matroska, WebM and AVI videos
+///
+/// Phones write the local time with its offset into the QuickTime metadata, other MP4 and
+/// QuickTime files have a `©day` item or the creation time in the movie header. Matroska and
+/// WebM files have a `DateUTC` in their segment info. AVI files from cameras have an `IDIT`
+/// chunk in their header list, others an `ICRD` in their `INFO` list.
+pub struct VideoMetadata;
+
+impl DateSource for VideoMetadata {
+    fn name(&self) -> &str {
+        "video metadata"
+    }
+
+    fn find_date(&self, path: &Path, _metadata: &Metadata, header: &[u8]) -> Option<FoundDate> {
+        match sniff(header)? {
+            FileType::Mp4 | FileType::Mov | FileType::M4v | FileType::ThreeGp => {
+                quicktime_date(path)
+            }
+            FileType::Mkv | FileType::Webm => matroska_date(path),
+            FileType::Avi => avi_date(path),
+            _ => None,
+        }
+    }
+}
+
+fn quicktime_date(path: &Path) -> Option<FoundDate> {
+    let moov = read_moov(&mut File::open(path).ok()?)?;
+    let text = |value: &[u8]| parse_video_date(std::str::from_utf8(value).ok()?);
+    let creation_date = find_atom(&moov, b"meta")
+        .and_then(|meta| metadata_value(meta, b"com.apple.quicktime.creationdate"))
+        .and_then(text);
+    if let Some(date) = creation_date {
+        return Some(FoundDate::new(date, Confidence::High));
+    }
+    if let Some(date) = movie_creation_time(&moov) {
+        let date: DateTime<Local> = date.into();
+        return Some(FoundDate::new(date.fixed_offset(), Confidence::High));
+    }
+    // Editors write `©day` when the movie header has no time, it may be the export time
+    let day = user_data_value(&moov, b"\xA9day").and_then(text)?;
+    Some(FoundDate::new(day, Confidence::Medium))
+}
+
+fn matroska_date(path: &Path) -> Option<FoundDate> {
+    read_matroska(path)?.date
+}
+
+/// What is read from the segment info and the first video track of a Matroska file
+#[derive(Default)]
+struct Matroska {
+    date: Option<FoundDate>,
+    duration: Option<Duration>,
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+fn read_matroska(path: &Path) -> Option<Matroska> {
+    let mut data = vec![];
+    File::open(path)
+        .ok()?
+        .take(MATROSKA_LENGTH)
+        .read_to_end(&mut data)
+        .ok()?;
+    // The EBML header, then the segment that holds everything else
+    let (_, segment) = elements(&data).into_iter().find(|&(id, _)| id == SEGMENT)?;
+
+    let mut matroska = Matroska::default();
+    for (id, body) in elements(segment) {
+        match id {
+            INFO => {
+                let mut timecode_scale = 1_000_000;
+                let mut duration = None;
+                for (id, value) in elements(body) {
+                    match id {
+                        DATE_UTC if value.len() == 8 => {
+                            // Nanoseconds since the start of the millennium
+                            let nanoseconds = i64::from_be_bytes(value.try_into().ok()?);
+                            let millennium = Utc.with_ymd_and_hms(2001, 1, 1, 0, 0, 0).single()?;
+                            let date: DateTime<Local> =
+                                (millennium + TimeDelta::nanoseconds(nanoseconds)).into();
+                            matroska.date =
+                                Some(FoundDate::new(date.fixed_offset(), Confidence::High));
+                        }
+                        TIMECODE_SCALE => timecode_scale = unsigned(value)?,
+                        DURATION => duration = float(value),
+                        _ => {}
+                    }
+                }
+                // The duration is counted in ticks of the timecode scale, in nanoseconds
+                matroska.duration = duration.and_then(|duration| {
+                    Duration::try_from_secs_f64(duration * timecode_scale as f64 / 1e9).ok()
+                });
+            }
+            TRACKS => {
+                let video = elements(body)
+                    .into_iter()
+                    .filter(|&(id, _)| id == TRACK_ENTRY)
+                    .find_map(|(_, entry)| {
+                        elements(entry).into_iter().find(|&(id, _)| id == VIDEO)
+                    });
+                for (id, value) in video.map(|(_, video)| elements(video)).unwrap_or_default() {
+                    match id {
+                        PIXEL_WIDTH => matroska.width = u32::try_from(unsigned(value)?).ok(),
+                        PIXEL_HEIGHT => matroska.height = u32::try_from(unsigned(value)?).ok(),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(matroska)
+}
+
+/// The IDs and contents of the elements in `data`, up to the first cluster
+///
+/// Elements of unknown size, or larger than what was read, run to the end of `data`.
+fn elements(data: &[u8]) -> Vec<(u32, &[u8])> {
+    let mut reader = Ebml { data, position: 0 };
+    let mut elements = vec![];
+    while let Some((id, size)) = reader.element() {
+        let end = size.map_or(data.len(), |size| {
+            reader.position.saturating_add(size).min(data.len())
+        });
+        let Some(body) = data.get(reader.position..end) else {
+            break;
+        };
+        elements.push((id, body));
+        if id == CLUSTER {
+            break;
+        }
+        reader.position = end;
+    }
+    elements
+}
+
+fn unsigned(value: &[u8]) -> Option<u64> {
+    (value.len() <= 8).then(|| {
+        value
+            .iter()
+            .fold(0, |number, &byte| (number << 8) | u64::from(byte))
+    })
+}
+
+fn float(value: &[u8]) -> Option<f64> {
+    match value.len() {
+        4 => Some(f32::from_be_bytes(value.try_into().ok()?).into()),
+        8 => Some(f64::from_be_bytes(value.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+/// Reads the IDs and sizes of EBML elements, leaving `position` at their data
+struct Ebml<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Ebml<'_> {
+    /// The ID and size of the next element, the size is `None` when it is unknown
+    fn element(&mut self) -> Option<(u32, Option<usize>)> {
+        let (id, id_length) = self.variable_integer()?;
+        // IDs keep their length marker
+        let id = u32::try_from(id | (1 << (7 * id_length))).ok()?;
+        let (size, size_length) = self.variable_integer()?;
+        let unknown = size == (1 << (7 * size_length)) - 1;
+        Some((id, (!unknown).then(|| usize::try_from(size).ok()).flatten()))
+    }
+
+    /// A variable length integer without its length marker, and its length in bytes
+    fn variable_integer(&mut self) -> Option<(u64, u32)> {
+        let first = *self.data.get(self.position)?;
+        let length = first.leading_zeros() + 1;
+        if length > 8 {
+            return None;
+        }
+        let mut value = u64::from(first) & (0xFF >> length);
+        for index in 1..length as usize {
+            value = (value << 8) | u64::from(*self.data.get(self.position + index)?);
+        }
+        self.position += length as usize;
+        Some((value, length))
+    }
+}
+
+fn avi_date(path: &Path) -> Option<FoundDate> {
+    let mut dates = vec![];
+    for list in avi_lists(path)? {
+        riff_chunks(&list, &mut |id, data| {
+            if !matches!(id, b"IDIT" | b"ICRD") {
+                return;
+            }
+            let text = String::from_utf8_lossy(data);
+            let text = text.trim_end_matches(['\0', '\n', '\r']).trim();
+            let confidence = if id == b"IDIT" {
+                Confidence::High
+            } else {
+                Confidence::Medium
+            };
+            if let Some(date) = parse_video_date(text) {
+                dates.push(FoundDate::new(date, confidence));
+            }
+        });
+    }
+    // The digitization date from the camera is more trusted than the creation date
+    dates.into_iter().reduce(|best, found| {
+        if found.confidence > best.confidence {
+            found
+        } else {
+            best
+        }
+    })
+}
+
+/// The contents of the `hdrl` and `INFO` lists of an AVI file, after their list type
+fn avi_lists(path: &Path) -> Option<Vec<Vec<u8>>> {
+    let mut file = File::open(path).ok()?;
+    file.seek(SeekFrom::Start(12)).ok()?;
+    let mut lists = vec![];
+    loop {
+        let mut header = [0; 12];
+        if file.read_exact(&mut header[..8]).is_err() {
+            break;
+        }
+        let length = u32::from_le_bytes(header[4..8].try_into().ok()?);
+        // Chunks are padded to an even length
+        let padded = i64::from(length) + i64::from(length % 2);
+        if &header[..4] == b"LIST" && length >= 4 {
+            file.read_exact(&mut header[8..]).ok()?;
+            if matches!(&header[8..], b"hdrl" | b"INFO") && length <= MAX_LIST_LENGTH {
+                let mut list = vec![0; length as usize - 4];
+                file.read_exact(&mut list).ok()?;
+                file.seek(SeekFrom::Current(padded - i64::from(length)))
+                    .ok()?;
+                lists.push(list);
+                continue;
+            }
+            file.seek(SeekFrom::Current(padded - 4)).ok()?;
+            continue;
+        }
+        file.seek(SeekFrom::Current(padded)).ok()?;
+    }
+    Some(lists)
+}
+
+/// Calls `visit` with the ID and data of each chunk in a list, and in the lists inside it
+fn riff_chunks(list: &[u8], visit: &mut impl FnMut(&[u8], &[u8])) {
+    riff_chunks_at(list, 0, visit);
+}
+
+/// Lists nested deeper than [`MAX_LIST_DEPTH`] are skipped, so they can't overflow the stack
+fn riff_chunks_at(mut list: &[u8], depth: usize, visit: &mut impl FnMut(&[u8], &[u8])) {
+    while list.len() >= 8 {
+        let id = &list[..4];
+        let length = u32::from_le_bytes([list[4], list[5], list[6], list[7]]) as usize;
+        let Some(data) = list.get(8..8 + length) else {
+            return;
+        };
+        if id == b"LIST" {
+            if depth < MAX_LIST_DEPTH {
+                riff_chunks_at(data.get(4..).unwrap_or_default(), depth + 1, visit);
+            }
+        } else {
+            visit(id, data);
+        }
+        list = list.get(8 + length + length % 2..).unwrap_or_default();
+    }
+}
+
+/// Duration and size of Matroska, WebM and AVI videos for name placeholders
+pub(crate) fn video_info(path: &Path, file_type: FileType) -> Option<MediaInfo> {
+    match file_type {
+        FileType::Mkv | FileType::Webm => {
+            let matroska = read_matroska(path)?;
+            Some(MediaInfo {
+                duration: matroska.duration,
+                width: matroska.width,
+                height: matroska.height,
+                ..MediaInfo::default()
+            })
+        }
+        FileType::Avi => {
+            let mut info = MediaInfo::default();
+            for list in avi_lists(path)? {
+                riff_chunks(&list, &mut |id, data| {
+                    if id != b"avih" {
+                        return;
+                    }
+                    let field = |offset: usize| {
+                        Some(u32::from_le_bytes(
+                            data.get(offset..offset + 4)?.try_into().ok()?,
+                        ))
+                    };
+                    // Microseconds per frame and total frames, then the frame size
+                    if let (Some(frame), Some(frames)) = (field(0), field(16)) {
+                        info.duration =
+                            Some(Duration::from_micros(u64::from(frame) * u64::from(frames)));
+                    }
+                    info.width = field(32).filter(|&width| width > 0);
+                    info.height = field(36).filter(|&height| height > 0);
+                });
+            }
+            Some(info)
+        }
+        _ => None,
+    }
+}
+
+/// `Mon Jul 17 14:30:00 2024` as AVI cameras write it, `2024-07-17T14:30:00+0200` as phones
+/// write it, EXIF style or ISO 8601 dates
+fn parse_video_date(text: &str) -> Option<DateTime<chrono::FixedOffset>> {
+    let text = text.trim_end_matches(['\0', '\n']);
+    let words = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    NaiveDateTime::parse_from_str(&words, "%a %b %d %H:%M:%S %Y")
+        .ok()
+        .and_then(|date| Some(Local.from_local_datetime(&date).earliest()?.fixed_offset()))
+        .or_else(|| DateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%z").ok())
+        .or_else(|| exif::parse_date(text, None))
+        .or_else(|| xmp::parse_date(text))
+        .or_else(|| xmp::parse_date(&text.replacen(' ', "T", 1)))
+}
@@ -0,0 +1,227 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use notify::{
+    event::{AccessKind, AccessMode, ModifyKind, RenameMode},
+    Event, EventKind, RecursiveMode, Watcher,
+};
+use owo_colors::OwoColorize;
+use tokio::{
+    fs, signal,
+    sync::mpsc,
+    time::{interval, sleep},
+};
+
+use crate::{args::WatchArgs, FileStatus, RenameOptions, Renamer};
+
+/// A file that arrived in the source folder and may still be written to
+struct PendingFile {
+    size: Option<u64>,
+    changed: Instant,
+    /// Closed after writing or moved in whole, so it only has to settle for the debounce time
+    closed: bool,
+}
+
+impl PendingFile {
+    fn new(closed: bool) -> Self {
+        PendingFile {
+            size: None,
+            changed: Instant::now(),
+            closed,
+        }
+    }
+}
+
+/// Rename files as they arrive in the source folder until Ctrl-C is pressed
+///
+/// Files are renamed once they were closed after writing and then left alone for
+/// `--debounce`, or when their size didn't change for `--settle`. On Ctrl-C the
+/// files that are no longer growing are renamed before exiting.
+///
+/// A target folder inside the source folder is left alone, so copies aren't renamed again.
+pub async fn run(options: RenameOptions, watch_args: &WatchArgs) -> Result<()> {
+    let settle = Duration::from_millis(watch_args.settle);
+    let debounce = Duration::from_millis(watch_args.debounce);
+    // Event paths start with the watched path, so both are compared without `..` or links
+    let source = fs::canonicalize(&options.source).await?;
+    let target = if options.preview {
+        None
+    } else {
+        fs::create_dir_all(&options.target).await?;
+        let target = fs::canonicalize(&options.target).await?;
+        if source.starts_with(&target) {
+            bail!("the target folder can't be the watched source folder or contain it");
+        }
+        Some(target)
+    };
+    let renamer = Renamer::new(options.source(&source));
+
+    let (sender, mut events) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = sender.send(event);
+    })?;
+    watcher.watch(&source, RecursiveMode::NonRecursive)?;
+    println!(
+        "Watching {} for new files. Press {} to stop.",
+        source.display().blue(),
+        "Ctrl-C".yellow()
+    );
+
+    let mut pending: HashMap<PathBuf, PendingFile> = HashMap::new();
+    let mut renamed = 0;
+    let mut ticks = interval(debounce.min(settle).max(Duration::from_millis(10)));
+    let shutdown = signal::ctrl_c();
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            Some(event) = events.recv() => track(&mut pending, event, target.as_deref()),
+            _ = ticks.tick() => {
+                let ready = ready_files(&mut pending, |file| {
+                    let waited = file.changed.elapsed();
+                    waited >= settle || (file.closed && waited >= debounce)
+                })
+                .await;
+                renamed += rename(&renamer, ready).await?;
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+
+    while let Ok(event) = events.try_recv() {
+        track(&mut pending, event, target.as_deref());
+    }
+    // Files that arrived since the last check are measured once more after the debounce
+    // time, files that grew in between are still being written and left alone
+    let mut ready = ready_files(&mut pending, |_| true).await;
+    if !pending.is_empty() {
+        sleep(debounce).await;
+        ready.extend(ready_files(&mut pending, |_| true).await);
+    }
+    renamed += rename(&renamer, ready).await?;
+    for path in pending.keys() {
+        eprintln!(
+            "{} {} {}",
+            " WARNING ".black().on_yellow(),
+            path.display().blue(),
+            "was still being written. Skipping.".yellow()
+        );
+    }
+    println!(
+        "{} {}",
+        renamed.green(),
+        "Files renamed while watching".green()
+    );
+    Ok(())
+}
+
+fn track(
+    pending: &mut HashMap<PathBuf, PendingFile>,
+    event: notify::Result<Event>,
+    target: Option<&Path>,
+) {
+    let mut event = match event {
+        Ok(event) => event,
+        Err(err) => {
+            eprintln!("{} {}", " ERROR ".black().on_red(), err.red());
+            return;
+        }
+    };
+    if let Some(target) = target {
+        event.paths.retain(|path| !path.starts_with(target));
+    }
+    match event.kind {
+        EventKind::Create(_) | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Any) => {
+            for path in event.paths {
+                let file = pending
+                    .entry(path)
+                    .or_insert_with(|| PendingFile::new(false));
+                file.changed = Instant::now();
+                file.closed = false;
+            }
+        }
+        EventKind::Access(AccessKind::Close(AccessMode::Write)) => {
+            for path in event.paths {
+                let file = pending
+                    .entry(path)
+                    .or_insert_with(|| PendingFile::new(true));
+                file.changed = Instant::now();
+                file.closed = true;
+            }
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+            for path in event.paths {
+                pending.insert(path, PendingFile::new(true));
+            }
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            let mut paths = event.paths.into_iter();
+            if let Some(from) = paths.next() {
+                pending.remove(&from);
+            }
+            for path in paths {
+                pending.insert(path, PendingFile::new(true));
+            }
+        }
+        EventKind::Modify(ModifyKind::Name(_)) | EventKind::Remove(_) => {
+            for path in event.paths {
+                pending.remove(&path);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Take the files out of `pending` that stopped growing and are `settled`
+async fn ready_files(
+    pending: &mut HashMap<PathBuf, PendingFile>,
+    settled: impl Fn(&PendingFile) -> bool,
+) -> Vec<PathBuf> {
+    let mut ready = vec![];
+    let mut gone = vec![];
+    for (path, file) in pending.iter_mut() {
+        let metadata = match fs::metadata(path).await {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => {
+                gone.push(path.clone());
+                continue;
+            }
+        };
+        let previous_size = file.size.replace(metadata.len());
+        if previous_size.is_some_and(|size| size != metadata.len()) {
+            file.changed = Instant::now();
+            continue;
+        }
+        // Without a close event a file has to be seen twice to know it stopped growing
+        if previous_size.is_none() && !file.closed {
+            continue;
+        }
+        if settled(file) {
+            ready.push(path.clone());
+        }
+    }
+    for path in gone.iter().chain(&ready) {
+        pending.remove(path);
+    }
+    ready
+}
+
+async fn rename(renamer: &Renamer, paths: Vec<PathBuf>) -> Result<u32> {
+    if paths.is_empty() {
+        return Ok(0);
+    }
+    let report = renamer.rename_paths(paths).await?;
+    for file in &report.files {
+        if let FileStatus::Renamed = file.status {
+            println!(
+                "{} {}",
+                file.source.display(),
+                file.destination.display().green()
+            );
+        }
+    }
+    Ok(report.count.renamed)
+}
@@ -0,0 +1,221 @@
+use std::{
+    fs::{File, Metadata},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+use crate::{
+    date_source::{Confidence, DateSource, FoundDate},
+    exif,
+    file_type::{sniff, FileType},
+};
+
+/// Largest packet that is parsed, real ones are a few kilobytes
+const MAX_PACKET_LENGTH: usize = 4 * 1024 * 1024;
+
+const CHUNK_LENGTH: usize = 1024 * 1024;
+
+/// Bytes searched at the start of files
+const HEADER_SCAN_LENGTH: u64 = 1024 * 1024;
+
+/// Bytes searched at the end of files that can keep their packet there, like PDF
+const TRAILER_SCAN_LENGTH: u64 = 1024 * 1024;
+
+const PACKET_STARTS: &[&[u8]] = &[b"<x:xmpmeta", b"<x:xapmeta"];
+const PACKET_ENDS: &[&[u8]] = &[b"</x:xmpmeta>", b"</x:xapmeta>"];
+
+const EXIF_NAMESPACE: &str = "http://ns.adobe.com/exif/1.0/";
+const PHOTOSHOP_NAMESPACE: &str = "http://ns.adobe.com/photoshop/1.0/";
+const XMP_NAMESPACE: &str = "http://ns.adobe.com/xap/1.0/";
+
+/// Dates in XMP, most trusted first
+const DATE_PROPERTIES: &[(&str, &str, Confidence)] = &[
+    (EXIF_NAMESPACE, "DateTimeOriginal", Confidence::High),
+    (PHOTOSHOP_NAMESPACE, "DateCreated", Confidence::High),
+    (XMP_NAMESPACE, "CreateDate", Confidence::Medium),
+];
+
+/// The date in an `.xmp` sidecar, or in the XMP packet embedded in the file
+///
+/// `exif:DateTimeOriginal` is used before `photoshop:DateCreated` and `xmp:CreateDate`.
+/// Sidecars come first, they hold the changes made in editors like Lightroom or darktable.
+pub struct Xmp;
+
+impl DateSource for Xmp {
+    fn name(&self) -> &str {
+        "xmp"
+    }
+
+    fn find_date(&self, path: &Path, _metadata: &Metadata, header: &[u8]) -> Option<FoundDate> {
+        let is_sidecar = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("xmp"));
+        if !is_sidecar {
+            let sidecar = sidecar_paths(path)
+                .into_iter()
+                .find_map(|sidecar| File::open(sidecar).ok())
+                .map(|sidecar| sidecar.take(MAX_PACKET_LENGTH as u64));
+            if let Some(found) = sidecar.and_then(find_packet).and_then(|p| packet_date(&p)) {
+                return Some(found);
+            }
+        }
+        // Packets come before the image data in JPEG files, TIFF and raw files point to
+        // theirs, elsewhere they can also be at the end. PNG and WebP chunks are read by
+        // `ImageMetadata`
+        let packet = match sniff(header) {
+            // Longer packets aren't parsed anyway
+            _ if is_sidecar => find_packet(File::open(path).ok()?.take(MAX_PACKET_LENGTH as u64)),
+            Some(FileType::Jpeg | FileType::Raf) => scan(path, false),
+            Some(FileType::Tiff | FileType::Orf | FileType::Rw2) => {
+                tiff_packet(path).or_else(|| scan(path, true))
+            }
+            Some(FileType::Gif | FileType::Pdf) => scan(path, true),
+            _ => return None,
+        }?;
+        packet_date(&packet)
+    }
+}
+
+/// The packet at the offset in the `XMLPacket` tag of TIFF and raw files
+fn tiff_packet(path: &Path) -> Option<String> {
+    let mut file = File::open(path).ok()?;
+    let mut start = vec![];
+    (&mut file)
+        .take(HEADER_SCAN_LENGTH)
+        .read_to_end(&mut start)
+        .ok()?;
+    let (offset, length) = exif::xmp_location(&start)?;
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    find_packet(file.take(length.min(MAX_PACKET_LENGTH as u64)))
+}
+
+/// Search the start of the file, and its end when `trailer` is set
+fn scan(path: &Path, trailer: bool) -> Option<String> {
+    let mut file = File::open(path).ok()?;
+    if let Some(packet) = find_packet((&mut file).take(HEADER_SCAN_LENGTH)) {
+        return Some(packet);
+    }
+    let length = file.metadata().ok()?.len();
+    if !trailer || length <= HEADER_SCAN_LENGTH {
+        return None;
+    }
+    let start = length
+        .saturating_sub(TRAILER_SCAN_LENGTH)
+        .max(HEADER_SCAN_LENGTH);
+    file.seek(SeekFrom::Start(start)).ok()?;
+    find_packet(file)
+}
+
+/// `IMG_1234.xmp` and `IMG_1234.CR2.xmp` for `IMG_1234.CR2`
+fn sidecar_paths(path: &Path) -> Vec<PathBuf> {
+    let mut paths = vec![];
+    for extension in ["xmp", "XMP"] {
+        paths.push(path.with_extension(extension));
+        let mut name = path.as_os_str().to_owned();
+        name.push(".");
+        name.push(extension);
+        paths.push(name.into());
+    }
+    paths
+}
+
+/// Read until the first XMP packet, in chunks so packets split between them are found too
+pub(crate) fn find_packet(mut reader: impl Read) -> Option<String> {
+    let mut buffer = vec![];
+    let mut chunk = vec![0; CHUNK_LENGTH];
+    let mut start = None;
+    loop {
+        let read = reader.read(&mut chunk).ok()?;
+        if read == 0 {
+            return None;
+        }
+        let searched = buffer.len();
+        buffer.extend_from_slice(&chunk[..read]);
+
+        if start.is_none() {
+            // Markers can be split between chunks
+            let from = searched.saturating_sub(16);
+            start = PACKET_STARTS
+                .iter()
+                .filter_map(|marker| find(&buffer[from..], marker))
+                .min()
+                .map(|position| position + from);
+            match start {
+                Some(position) => {
+                    buffer.drain(..position);
+                }
+                None => {
+                    let keep = buffer.len().saturating_sub(16);
+                    buffer.drain(..keep);
+                    continue;
+                }
+            }
+        }
+        if let Some(end) = PACKET_ENDS
+            .iter()
+            .filter_map(|marker| find(&buffer, marker).map(|position| position + marker.len()))
+            .min()
+        {
+            return String::from_utf8(buffer[..end].to_vec()).ok();
+        }
+        if buffer.len() > MAX_PACKET_LENGTH {
+            return None;
+        }
+    }
+}
+
+/// The most trusted date in an XMP packet
+pub(crate) fn packet_date(packet: &str) -> Option<FoundDate> {
+    let document = roxmltree::Document::parse(packet).ok()?;
+    DATE_PROPERTIES
+        .iter()
+        .find_map(|&(namespace, name, confidence)| {
+            let value = document.descendants().find_map(|node| {
+                if !node.is_element() {
+                    return None;
+                }
+                if let Some(value) = node.attribute((namespace, name)) {
+                    return Some(value);
+                }
+                let tag = node.tag_name();
+                (tag.namespace() == Some(namespace) && tag.name() == name)
+                    .then(|| node.text())
+                    .flatten()
+            })?;
+            Some(FoundDate::new(parse_date(value)?, confidence))
+        })
+}
+
+/// ISO 8601 dates as written in XMP, without a time zone they are in local time
+///
+/// `2024-07-17T14:30:00.25+02:00`, `2024-07-17T14:30Z`, `2024-07-17T14:30:00` and
+/// `2024-07-17` are all valid.
+pub(crate) fn parse_date(value: &str) -> Option<DateTime<FixedOffset>> {
+    let value = value.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date);
+    }
+    let value = value.replacen('Z', "+00:00", 1);
+    for format in ["%Y-%m-%dT%H:%M:%S%.f%:z", "%Y-%m-%dT%H:%M%:z"] {
+        if let Ok(date) = DateTime::parse_from_str(&value, format) {
+            return Some(date);
+        }
+    }
+    let naive = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(&value, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+                .ok()?
+                .and_hms_opt(0, 0, 0)
+        })?;
+    Some(Local.from_local_datetime(&naive).earliest()?.fixed_offset())
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
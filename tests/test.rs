@@ -1,9 +1,9 @@
 use assert_cmd::assert::OutputAssertExt;
 use assert_cmd::Command;
 use assert_fs::prelude::PathCreateDir;
-use assert_fs::prelude::{FileTouch, PathChild};
+use assert_fs::prelude::{FileTouch, FileWriteBin, FileWriteStr, PathChild};
 use chrono::{DateTime, Local, Utc};
-use predicates::prelude::predicate;
+use predicates::prelude::{predicate, PredicateBooleanExt};
 use std::{
     fs::read_dir,
     io::{self, Write},
@@ -28,13 +28,152 @@ fn setup() -> (
     (temp, now_formatted)
 }
 
+/// The binary without the developer's own config file or `CREATEDAT_*` variables
+fn createdat() -> Command {
+    let mut cmd = Command::cargo_bin("createdat").unwrap();
+    let config_home = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("config");
+    cmd.env("XDG_CONFIG_HOME", config_home);
+    for (key, _) in std::env::vars_os() {
+        if key.to_string_lossy().starts_with("CREATEDAT_") {
+            cmd.env_remove(key);
+        }
+    }
+    cmd
+}
+
+// Fixtures, the smallest files with the metadata the tests need
+
+const PNG_HEADER: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x01\0\0\0\x01\x08\x06\0\0\0";
+
+fn png_chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(kind);
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(&[0; 4]);
+    chunk
+}
+
+fn riff_chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut chunk = kind.to_vec();
+    chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        chunk.push(0);
+    }
+    chunk
+}
+
+fn atom(kind: &[u8], body: &[u8]) -> Vec<u8> {
+    let mut atom = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+    atom.extend_from_slice(kind);
+    atom.extend_from_slice(body);
+    atom
+}
+
+fn ogg_page(flags: u8, packet: &[u8]) -> Vec<u8> {
+    let mut page = b"OggS\0".to_vec();
+    page.push(flags);
+    page.extend_from_slice(&[0; 20]);
+    page.push(1);
+    page.push(packet.len() as u8);
+    page.extend_from_slice(packet);
+    page
+}
+
+fn vorbis_comments(comments: &[&str]) -> Vec<u8> {
+    let mut data = 6u32.to_le_bytes().to_vec();
+    data.extend_from_slice(b"vendor");
+    data.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for comment in comments {
+        data.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        data.extend_from_slice(comment.as_bytes());
+    }
+    data
+}
+
+/// A tag, type, count and big endian value of an IFD entry
+type TiffEntry = (u16, u16, u32, Vec<u8>);
+
+fn ascii(tag: u16, text: &str) -> TiffEntry {
+    let mut value = text.as_bytes().to_vec();
+    value.push(0);
+    (tag, 2, value.len() as u32, value)
+}
+
+fn short(tag: u16, value: u16) -> TiffEntry {
+    (tag, 3, 1, value.to_be_bytes().to_vec())
+}
+
+fn long(tag: u16, value: u32) -> TiffEntry {
+    (tag, 4, 1, value.to_be_bytes().to_vec())
+}
+
+fn rationals(tag: u16, values: &[u32]) -> TiffEntry {
+    let value = values
+        .iter()
+        .flat_map(|value| [value.to_be_bytes(), 1u32.to_be_bytes()].concat())
+        .collect();
+    (tag, 5, values.len() as u32, value)
+}
+
+/// Big endian EXIF data, the EXIF and GPS IFDs are only written when they have entries
+fn tiff(ifd0: &[TiffEntry], exif: &[TiffEntry], gps: &[TiffEntry]) -> Vec<u8> {
+    // Values longer than four bytes are stored after the IFD
+    fn ifd(start: u32, entries: &[TiffEntry]) -> Vec<u8> {
+        let mut data_offset = start + 2 + 12 * entries.len() as u32 + 4;
+        let mut ifd = (entries.len() as u16).to_be_bytes().to_vec();
+        let mut data = vec![];
+        for (tag, kind, count, value) in entries {
+            ifd.extend_from_slice(&tag.to_be_bytes());
+            ifd.extend_from_slice(&kind.to_be_bytes());
+            ifd.extend_from_slice(&count.to_be_bytes());
+            if value.len() <= 4 {
+                let mut inline = value.clone();
+                inline.resize(4, 0);
+                ifd.extend(inline);
+            } else {
+                ifd.extend_from_slice(&data_offset.to_be_bytes());
+                data_offset += value.len() as u32;
+                data.extend_from_slice(value);
+            }
+        }
+        ifd.extend_from_slice(&[0; 4]);
+        ifd.extend(data);
+        ifd
+    }
+    let sub_ifd = |start: u32, entries: &[TiffEntry]| {
+        if entries.is_empty() {
+            vec![]
+        } else {
+            ifd(start, entries)
+        }
+    };
+    let ifd0 = |exif_start: u32, gps_start: u32| {
+        let mut entries = ifd0.to_vec();
+        if !exif.is_empty() {
+            entries.push(long(0x8769, exif_start));
+        }
+        if !gps.is_empty() {
+            entries.push(long(0x8825, gps_start));
+        }
+        ifd(8, &entries)
+    };
+    let exif_start = 8 + ifd0(0, 0).len() as u32;
+    let gps_start = exif_start + sub_ifd(exif_start, exif).len() as u32;
+    let mut data = b"MM\0*\0\0\0\x08".to_vec();
+    data.extend(ifd0(exif_start, gps_start));
+    data.extend(sub_ifd(exif_start, exif));
+    data.extend(sub_ifd(gps_start, gps));
+    data
+}
+
 #[test]
 fn no_flags() {
     let temp = assert_fs::TempDir::new().unwrap();
     let test_image = temp.child("test.jpg");
     test_image.touch().unwrap();
 
-    let mut cmd = Command::cargo_bin("createdat").unwrap();
+    let mut cmd = createdat();
     let now = Utc::now();
     let now_local: DateTime<Local> = now.into();
     let now_formatted = now_local.format("%Y-%m-%d_%H-%M-%S");
@@ -60,7 +199,7 @@ fn no_flags() {
 fn all() {
     let (temp, now_formatted) = setup();
 
-    let mut cmd = Command::cargo_bin("createdat").unwrap();
+    let mut cmd = createdat();
 
     let output = cmd.current_dir(temp.path()).arg("-a").output().unwrap();
     io::stdout().write_all(&output.stdout).unwrap();
@@ -89,7 +228,7 @@ fn all() {
 fn no_name() {
     let (temp, now_formatted) = setup();
 
-    let mut cmd = Command::cargo_bin("createdat").unwrap();
+    let mut cmd = createdat();
 
     let output = cmd.current_dir(temp.path()).arg("-an").output().unwrap();
     io::stdout().write_all(&output.stdout).unwrap();
@@ -117,7 +256,7 @@ fn no_name() {
 fn name() {
     let (temp, now_formatted) = setup();
 
-    let mut cmd = Command::cargo_bin("createdat").unwrap();
+    let mut cmd = createdat();
 
     let output = cmd
         .current_dir(temp.path())
@@ -150,7 +289,7 @@ fn name() {
 fn target() {
     let (temp, now_formatted) = setup();
 
-    let mut cmd = Command::cargo_bin("createdat").unwrap();
+    let mut cmd = createdat();
 
     let output = cmd
         .current_dir(temp.path())
@@ -183,7 +322,7 @@ fn target() {
 fn twelve() {
     let (temp, _) = setup();
 
-    let mut cmd = Command::cargo_bin("createdat").unwrap();
+    let mut cmd = createdat();
 
     let now = Utc::now();
     let now_local: DateTime<Local> = now.into();
@@ -214,7 +353,7 @@ fn twelve() {
 fn front() {
     let (temp, now_formatted) = setup();
 
-    let mut cmd = Command::cargo_bin("createdat").unwrap();
+    let mut cmd = createdat();
 
     let output = cmd
         .current_dir(temp.path())
@@ -246,7 +385,7 @@ fn front() {
 fn suffix() {
     let (temp, now_formatted) = setup();
 
-    let mut cmd = Command::cargo_bin("createdat").unwrap();
+    let mut cmd = createdat();
 
     let output = cmd
         .current_dir(temp.path())
@@ -278,7 +417,7 @@ fn suffix() {
 fn date() {
     let (temp, _) = setup();
 
-    let mut cmd = Command::cargo_bin("createdat").unwrap();
+    let mut cmd = createdat();
 
     let now = Utc::now();
     let now_local: DateTime<Local> = now.into();
@@ -315,7 +454,7 @@ fn source() {
     let test_dir = temp.child("test");
     test_dir.create_dir_all().unwrap();
 
-    let mut cmd = Command::cargo_bin("createdat").unwrap();
+    let mut cmd = createdat();
 
     let output = cmd
         .current_dir(temp.path().join(test_dir.path()))
@@ -348,7 +487,7 @@ fn source() {
 fn count() {
     let (temp, now_formatted) = setup();
 
-    let mut cmd = Command::cargo_bin("createdat").unwrap();
+    let mut cmd = createdat();
 
     let output = cmd.current_dir(temp.path()).arg("-a").output().unwrap();
 
@@ -377,3 +516,1721 @@ fn count() {
 
     temp.close().unwrap();
 }
+
+fn renamed_file_names(folder: &std::path::Path) -> Vec<String> {
+    let mut names: Vec<String> = read_dir(folder)
+        .unwrap()
+        .filter_map(Result::ok)
+        .map(|file| file.file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn ext() {
+    let (temp, now_formatted) = setup();
+    temp.child("photo.PNG").touch().unwrap();
+    temp.child("backup.tar.gz").touch().unwrap();
+    temp.child("notes.gz").touch().unwrap();
+
+    let mut cmd = createdat();
+
+    let output = cmd
+        .current_dir(temp.path())
+        .args(["--ext", "JPG,png,tar.gz"])
+        .output()
+        .unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+
+    assert_eq!(
+        vec![
+            format!("backup-{}.tar.gz", now_formatted),
+            format!("photo-{}.PNG", now_formatted),
+            format!("test-{}.jpg", now_formatted),
+        ],
+        renamed_file_names(&temp.path().join("renamed"))
+    );
+    temp.close().unwrap();
+}
+
+#[test]
+fn exclude_ext() {
+    let (temp, now_formatted) = setup();
+
+    let mut cmd = createdat();
+
+    let output = cmd
+        .current_dir(temp.path())
+        .args(["-a", "--exclude-ext", "MP4,gitignore"])
+        .output()
+        .unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+
+    assert_eq!(
+        vec![format!("test-{}.jpg", now_formatted)],
+        renamed_file_names(&temp.path().join("renamed"))
+    );
+    temp.close().unwrap();
+}
+
+#[test]
+fn include_exclude() {
+    let (temp, now_formatted) = setup();
+    temp.child("IMG_0001.jpg").touch().unwrap();
+    temp.child("IMG_0002_edited.jpg").touch().unwrap();
+
+    let mut cmd = createdat();
+
+    let output = cmd
+        .current_dir(temp.path())
+        .args(["-a", "--include", "IMG_*", "--exclude", "*_edited.*"])
+        .output()
+        .unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+
+    assert_eq!(
+        vec![format!("IMG_0001-{}.jpg", now_formatted)],
+        renamed_file_names(&temp.path().join("renamed"))
+    );
+    temp.close().unwrap();
+}
+
+#[test]
+fn regex() {
+    let (temp, now_formatted) = setup();
+    temp.child("IMG_0001.jpg").touch().unwrap();
+    temp.child("IMG_ABCD.jpg").touch().unwrap();
+
+    let mut cmd = createdat();
+
+    let output = cmd
+        .current_dir(temp.path())
+        .args([
+            "--regex",
+            "--include",
+            r"^IMG_\d+\.",
+            "--include",
+            r"^test\.mp4$",
+        ])
+        .args(["-a"])
+        .output()
+        .unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+
+    assert_eq!(
+        vec![
+            format!("IMG_0001-{}.jpg", now_formatted),
+            format!("test-{}.mp4", now_formatted),
+        ],
+        renamed_file_names(&temp.path().join("renamed"))
+    );
+    temp.close().unwrap();
+}
+
+#[test]
+fn since_until() {
+    let (temp, now_formatted) = setup();
+    let old_image = temp.child("old.jpg");
+    old_image.touch().unwrap();
+    let old_time = std::time::SystemTime::now() - std::time::Duration::from_secs(60 * 60 * 24 * 45);
+    std::fs::File::options()
+        .write(true)
+        .open(old_image.path())
+        .unwrap()
+        .set_modified(old_time)
+        .unwrap();
+    let old_formatted = DateTime::<Local>::from(old_time).format("%Y-%m-%d_%H-%M-%S");
+
+    let output = createdat()
+        .current_dir(temp.path())
+        .args(["--since", "30d", "-T", "recent"])
+        .output()
+        .unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+    output.assert().success().stdout(predicate::str::contains(
+        "Files outside of the date range were skipped",
+    ));
+    assert_eq!(
+        vec![format!("test-{}.jpg", now_formatted)],
+        renamed_file_names(&temp.path().join("recent"))
+    );
+
+    let until = DateTime::<Local>::from(old_time + std::time::Duration::from_secs(60 * 60 * 24))
+        .format("%Y-%m-%d")
+        .to_string();
+    let output = createdat()
+        .current_dir(temp.path())
+        .args(["--until", &until, "-T", "old"])
+        .output()
+        .unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+    assert_eq!(
+        vec![format!("old-{}.jpg", old_formatted)],
+        renamed_file_names(&temp.path().join("old"))
+    );
+
+    createdat()
+        .current_dir(temp.path())
+        .args(["--since", "100000000d", "-p"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is not a date"));
+    temp.close().unwrap();
+}
+
+#[test]
+fn sniff_content() {
+    let (temp, now_formatted) = setup();
+    temp.child("scan").write_binary(PNG_HEADER).unwrap();
+    temp.child("misnamed.jpg").write_binary(PNG_HEADER).unwrap();
+    temp.child("notes.txt").write_binary(PNG_HEADER).unwrap();
+
+    let output = createdat().current_dir(temp.path()).output().unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+    assert_eq!(
+        vec![
+            format!("misnamed-{}.jpg", now_formatted),
+            format!("notes-{}.txt", now_formatted),
+            format!("scan-{}", now_formatted),
+            format!("test-{}.jpg", now_formatted),
+        ],
+        renamed_file_names(&temp.path().join("renamed"))
+    );
+
+    let output = createdat()
+        .current_dir(temp.path())
+        .args(["--fix-extension", "-T", "fixed"])
+        .output()
+        .unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+    assert_eq!(
+        vec![
+            format!("misnamed-{}.png", now_formatted),
+            format!("notes-{}.png", now_formatted),
+            format!("scan-{}.png", now_formatted),
+            format!("test-{}.jpg", now_formatted),
+        ],
+        renamed_file_names(&temp.path().join("fixed"))
+    );
+    temp.close().unwrap();
+}
+
+#[test]
+fn categories() {
+    let (temp, now_formatted) = setup();
+    temp.child("clip.mov").touch().unwrap();
+    temp.child("song.mp3").touch().unwrap();
+    temp.child("notes.txt").touch().unwrap();
+    temp.child("report.pdf")
+        .write_binary(b"%PDF-1.7\n")
+        .unwrap();
+    temp.child("backup.zip")
+        .write_binary(b"PK\x03\x04")
+        .unwrap();
+
+    let output = createdat()
+        .current_dir(temp.path())
+        .args(["--images", "--videos"])
+        .output()
+        .unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+    assert_eq!(
+        vec![
+            format!("clip-{}.mov", now_formatted),
+            format!("test-{}.jpg", now_formatted),
+            format!("test-{}.mp4", now_formatted),
+        ],
+        renamed_file_names(&temp.path().join("renamed"))
+    );
+
+    let output = createdat()
+        .current_dir(temp.path())
+        .args(["--audio", "--documents", "--archives", "-T", "other"])
+        .output()
+        .unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+    assert_eq!(
+        vec![
+            format!("backup-{}.zip", now_formatted),
+            format!("notes-{}.txt", now_formatted),
+            format!("report-{}.pdf", now_formatted),
+            format!("song-{}.mp3", now_formatted),
+        ],
+        renamed_file_names(&temp.path().join("other"))
+    );
+    temp.close().unwrap();
+}
+
+#[tokio::test]
+async fn library() {
+    let (temp, now_formatted) = setup();
+
+    let options = createdat::RenameOptions::new(temp.path())
+        .target(temp.path().join("library"))
+        .name("trip")
+        .name_after_date(true)
+        .categories([createdat::Category::Image, createdat::Category::Video])
+        .preview(true);
+    let report = createdat::Renamer::new(options).run().await.unwrap();
+
+    assert_eq!(2, report.count.total);
+    assert!(!temp.path().join("library").exists());
+    let mut destinations: Vec<_> = report
+        .files
+        .iter()
+        .filter(|file| matches!(file.status, createdat::FileStatus::Previewed))
+        .map(|file| {
+            file.destination
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    destinations.sort();
+    assert_eq!(
+        vec![
+            format!("test-{}-trip.jpg", now_formatted),
+            format!("test-{}-trip.mp4", now_formatted),
+        ],
+        destinations
+    );
+    temp.close().unwrap();
+}
+
+#[tokio::test]
+async fn library_errors() {
+    let (temp, now_formatted) = setup();
+    temp.child("notes.txt").touch().unwrap();
+    temp.child("renamed")
+        .child(format!("test-{}.jpg", now_formatted))
+        .touch()
+        .unwrap();
+
+    let options = createdat::RenameOptions::new(temp.path())
+        .target(temp.path().join("renamed"))
+        .exclude("*.mp4");
+    let report = createdat::Renamer::new(options).run().await.unwrap();
+
+    let mut skipped: Vec<_> = report
+        .skipped
+        .iter()
+        .map(|file| {
+            (
+                file.path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned(),
+                file.reason,
+            )
+        })
+        .collect();
+    skipped.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        vec![
+            (".gitignore".to_owned(), createdat::SkipReason::Category),
+            ("notes.txt".to_owned(), createdat::SkipReason::Category),
+            ("renamed".to_owned(), createdat::SkipReason::Directory),
+            ("test.mp4".to_owned(), createdat::SkipReason::Pattern),
+        ],
+        skipped
+    );
+    assert_eq!(1, report.count.duplicate);
+    assert!(matches!(
+        report.files[0].status,
+        createdat::FileStatus::Failed(createdat::Error::Conflict { .. })
+    ));
+
+    let missing = createdat::RenameOptions::new(temp.path().join("missing")).preview(true);
+    let error = createdat::Renamer::new(missing).run().await.unwrap_err();
+    assert!(matches!(error, createdat::Error::Io { .. }));
+
+    let invalid = createdat::RenameOptions::new(temp.path())
+        .include("[")
+        .preview(true);
+    let error = createdat::Renamer::new(invalid).run().await.unwrap_err();
+    assert!(matches!(error, createdat::Error::Pattern(_)));
+    temp.close().unwrap();
+}
+
+#[test]
+fn duplicate() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let taken = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+    let taken_formatted = DateTime::<Local>::from(taken).format("%Y-%m-%d_%H-%M-%S");
+    for name in ["first.jpg", "second.jpg"] {
+        temp.child(name).touch().unwrap();
+        set_modified(&temp.path().join(name), taken);
+    }
+    let renamed = temp.child("renamed");
+    renamed.create_dir_all().unwrap();
+    renamed.child("notes.txt").touch().unwrap();
+    renamed
+        .child(format!("second-{taken_formatted}.jpg"))
+        .touch()
+        .unwrap();
+
+    let output = createdat().current_dir(temp.path()).output().unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+
+    // The copy made by this run is removed, the files that were there are kept
+    assert_eq!(
+        vec![
+            "notes.txt".to_owned(),
+            format!("second-{taken_formatted}.jpg")
+        ],
+        renamed_file_names(renamed.path())
+    );
+    temp.close().unwrap();
+}
+
+struct InstrumentHeader;
+
+impl createdat::DateSource for InstrumentHeader {
+    fn name(&self) -> &str {
+        "instrument header"
+    }
+
+    fn find_date(
+        &self,
+        _path: &std::path::Path,
+        _metadata: &std::fs::Metadata,
+        header: &[u8],
+    ) -> Option<createdat::FoundDate> {
+        let line = std::str::from_utf8(header.strip_prefix(b"TIMESTAMP=")?.get(..25)?).ok()?;
+        let date = DateTime::parse_from_rfc3339(line).ok()?;
+        Some(createdat::FoundDate::new(date, createdat::Confidence::High))
+    }
+}
+
+#[tokio::test]
+async fn date_source() {
+    let (temp, now_formatted) = setup();
+    temp.child("sample.dat")
+        .write_binary(b"TIMESTAMP=2021-03-04T05:06:07+09:00\nvalues...")
+        .unwrap();
+
+    let options = createdat::RenameOptions::new(temp.path())
+        .target(temp.path().join("renamed"))
+        .all_files()
+        .exclude(".gitignore")
+        .date_source(InstrumentHeader);
+    let report = createdat::Renamer::new(options).run().await.unwrap();
+
+    assert_eq!(3, report.count.renamed);
+    assert_eq!(
+        vec![
+            "sample-2021-03-04_05-06-07.dat".to_owned(),
+            format!("test-{}.jpg", now_formatted),
+            format!("test-{}.mp4", now_formatted),
+        ],
+        renamed_file_names(&temp.path().join("renamed"))
+    );
+    temp.close().unwrap();
+}
+
+#[test]
+fn sidecars() {
+    let (temp, now_formatted) = setup();
+    temp.child("IMG_1234.CR2").touch().unwrap();
+    temp.child("IMG_1234.xmp").touch().unwrap();
+    temp.child("IMG_1234.CR2.xmp").touch().unwrap();
+    temp.child("test.AAE").touch().unwrap();
+    temp.child("lonely.xmp").touch().unwrap();
+    temp.child("IMG_5678.CR2").touch().unwrap();
+    temp.child("IMG_5678.JPG").touch().unwrap();
+    temp.child("IMG_5678.CR2.xmp").touch().unwrap();
+
+    let mut cmd = createdat();
+
+    let output = cmd
+        .current_dir(temp.path())
+        .args(["--ext", "jpg,cr2"])
+        .output()
+        .unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+
+    assert_eq!(
+        vec![
+            format!("IMG_1234-{}.CR2", now_formatted),
+            format!("IMG_1234-{}.CR2.xmp", now_formatted),
+            format!("IMG_1234-{}.xmp", now_formatted),
+            format!("IMG_5678-{}.CR2", now_formatted),
+            format!("IMG_5678-{}.CR2.xmp", now_formatted),
+            format!("IMG_5678-{}.JPG", now_formatted),
+            format!("test-{}.AAE", now_formatted),
+            format!("test-{}.jpg", now_formatted),
+        ],
+        renamed_file_names(&temp.path().join("renamed"))
+    );
+
+    // The sidecar of the raw file isn't renamed after the JPEG taken with it
+    let mut cmd = createdat();
+    cmd.current_dir(temp.path())
+        .args(["--ext", "jpg", "-T", "jpegs"])
+        .assert()
+        .success();
+    assert_eq!(
+        vec![
+            format!("IMG_5678-{}.JPG", now_formatted),
+            format!("test-{}.AAE", now_formatted),
+            format!("test-{}.jpg", now_formatted),
+        ],
+        renamed_file_names(&temp.path().join("jpegs"))
+    );
+
+    let mut cmd = createdat();
+    cmd.current_dir(temp.path())
+        .args(["--ext", "jpg,cr2", "--no-sidecars", "-T", "alone"])
+        .assert()
+        .success();
+    assert_eq!(
+        vec![
+            format!("IMG_1234-{}.CR2", now_formatted),
+            format!("IMG_5678-{}.CR2", now_formatted),
+            format!("IMG_5678-{}.JPG", now_formatted),
+            format!("test-{}.jpg", now_formatted),
+        ],
+        renamed_file_names(&temp.path().join("alone"))
+    );
+    temp.close().unwrap();
+}
+
+fn set_modified(path: &std::path::Path, time: std::time::SystemTime) {
+    std::fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(time)
+        .unwrap();
+}
+
+#[test]
+fn pairs() {
+    let (temp, now_formatted) = setup();
+    let raw_time = std::time::SystemTime::now() - std::time::Duration::from_secs(60 * 60);
+    let raw_formatted = DateTime::<Local>::from(raw_time).format("%Y-%m-%d_%H-%M-%S");
+    temp.child("DSC_0001.NEF").touch().unwrap();
+    temp.child("DSC_0001.JPG").touch().unwrap();
+    set_modified(&temp.path().join("DSC_0001.NEF"), raw_time);
+    set_modified(
+        &temp.path().join("DSC_0001.JPG"),
+        raw_time + std::time::Duration::from_secs(1),
+    );
+
+    // Live Photo halves with different names, paired by their content identifier
+    let identifier = b"1D5B8E2A-5C4F-4C43-9E3A-0F6A2B7C9D10";
+    let mut maker_note = b"Apple iOS\0\0\x01MM\0\x01\0\x11\0\x02".to_vec();
+    maker_note.extend_from_slice(&(identifier.len() as u32 + 1).to_be_bytes());
+    maker_note.extend_from_slice(&32u32.to_be_bytes());
+    maker_note.extend_from_slice(&[0; 4]);
+    maker_note.extend_from_slice(identifier);
+    maker_note.push(0);
+    let mut heic = atom(b"ftyp", b"heic\0\0\0\0mif1heic");
+    heic.extend(atom(b"mdat", &maker_note));
+    temp.child("IMG_0002.HEIC").write_binary(&heic).unwrap();
+
+    let key = b"com.apple.quicktime.content.identifier";
+    let mut keys = vec![0, 0, 0, 0, 0, 0, 0, 1];
+    keys.extend(atom(b"mdta", key));
+    let mut value = vec![0, 0, 0, 1, 0, 0, 0, 0];
+    value.extend_from_slice(identifier);
+    let item = atom(&1u32.to_be_bytes(), &atom(b"data", &value));
+    let mut meta = atom(b"hdlr", &[0; 24]);
+    meta.extend(atom(b"keys", &keys));
+    meta.extend(atom(b"ilst", &item));
+    let mut mov = atom(b"ftyp", b"qt  \0\0\0\0qt  ");
+    mov.extend(atom(b"moov", &atom(b"meta", &meta)));
+    temp.child("IMG_0002_LIVE.MOV").write_binary(&mov).unwrap();
+    let live_time = raw_time - std::time::Duration::from_secs(60);
+    let live_formatted = DateTime::<Local>::from(live_time).format("%Y-%m-%d_%H-%M-%S");
+    set_modified(&temp.path().join("IMG_0002.HEIC"), live_time);
+
+    // The same name days apart, after the counter wrapped, isn't a pair
+    temp.child("IMG_0003.JPG").touch().unwrap();
+    temp.child("IMG_0003.MOV").touch().unwrap();
+    let wrapped_time = raw_time - std::time::Duration::from_secs(60 * 60 * 24 * 2);
+    let wrapped_formatted = DateTime::<Local>::from(wrapped_time).format("%Y-%m-%d_%H-%M-%S");
+    set_modified(&temp.path().join("IMG_0003.JPG"), wrapped_time);
+    set_modified(&temp.path().join("IMG_0003.MOV"), raw_time);
+
+    let mut cmd = createdat();
+
+    let output = cmd
+        .current_dir(temp.path())
+        .args(["--ext", "nef,jpg,heic,mov"])
+        .output()
+        .unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+
+    assert_eq!(
+        vec![
+            format!("DSC_0001-{}.JPG", raw_formatted),
+            format!("DSC_0001-{}.NEF", raw_formatted),
+            format!("IMG_0002-{}.HEIC", live_formatted),
+            format!("IMG_0002-{}.MOV", live_formatted),
+            format!("IMG_0003-{}.JPG", wrapped_formatted),
+            format!("IMG_0003-{}.MOV", raw_formatted),
+            format!("test-{}.jpg", now_formatted),
+        ],
+        renamed_file_names(&temp.path().join("renamed"))
+    );
+    temp.close().unwrap();
+}
+
+#[test]
+fn google_takeout() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let taken = |timestamp: i64| {
+        DateTime::from_timestamp(timestamp, 0)
+            .unwrap()
+            .with_timezone(&Local)
+            .format("%Y-%m-%d_%H-%M-%S")
+    };
+    let json = |timestamp: i64| {
+        format!(
+            "{{\"title\": \"photo.jpg\", \"photoTakenTime\": {{\"timestamp\": \"{timestamp}\"}}}}"
+        )
+    };
+    temp.child("photo.jpg").touch().unwrap();
+    temp.child("photo.jpg.json")
+        .write_str(&json(1577880000))
+        .unwrap();
+    temp.child("photo(1).jpg").touch().unwrap();
+    temp.child("photo.jpg.supplemental-metadata(1).json")
+        .write_str(&json(1577966400))
+        .unwrap();
+    temp.child("PXL_20200103_120000000.PORTRAIT-01.COVER.jpg")
+        .touch()
+        .unwrap();
+    temp.child("PXL_20200103_120000000.PORTRAIT-01.COVER.jpg.s.json")
+        .write_str(&json(1578052800))
+        .unwrap();
+
+    let mut cmd = createdat();
+
+    let output = cmd.current_dir(temp.path()).output().unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+
+    assert_eq!(
+        vec![
+            format!(
+                "PXL_20200103_120000000.PORTRAIT-01.COVER-{}.jpg",
+                taken(1578052800)
+            ),
+            format!("photo(1)-{}.jpg", taken(1577966400)),
+            format!("photo-{}.jpg", taken(1577880000)),
+        ],
+        renamed_file_names(&temp.path().join("renamed"))
+    );
+    temp.close().unwrap();
+}
+
+#[test]
+fn xmp() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let packet = |properties: &str| {
+        format!(
+            "<?xpacket begin=\"\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\
+             <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\
+             <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\
+             <rdf:Description rdf:about=\"\" \
+             xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\" \
+             xmlns:exif=\"http://ns.adobe.com/exif/1.0/\" \
+             xmlns:photoshop=\"http://ns.adobe.com/photoshop/1.0/\" {properties}>\
+             </rdf:Description></rdf:RDF></x:xmpmeta><?xpacket end=\"w\"?>"
+        )
+    };
+
+    let mut jpeg = b"\xFF\xD8\xFF\xE1\0\0http://ns.adobe.com/xap/1.0/\0".to_vec();
+    jpeg.extend_from_slice(
+        packet(
+            "xmp:CreateDate=\"2022-01-01T10:00:00Z\" \
+             exif:DateTimeOriginal=\"2021-05-06T07:08:09+02:00\"",
+        )
+        .as_bytes(),
+    );
+    temp.child("edited.jpg").write_binary(&jpeg).unwrap();
+
+    // TIFF files point to their packet, wherever it is
+    let packet_offset = 2 * 1024 * 1024u32;
+    let tiff_packet = packet("exif:DateTimeOriginal=\"2020-02-03T04:05:06\"");
+    let mut tiff = tiff(
+        &[(
+            0x02BC,
+            7,
+            tiff_packet.len() as u32,
+            packet_offset.to_be_bytes().to_vec(),
+        )],
+        &[],
+        &[],
+    );
+    tiff.resize(packet_offset as usize, 0);
+    tiff.extend_from_slice(tiff_packet.as_bytes());
+    tiff.resize(tiff.len() + 2 * 1024 * 1024, 0);
+    temp.child("scan.tif").write_binary(&tiff).unwrap();
+
+    temp.child("IMG_0001.JPG").touch().unwrap();
+    temp.child("IMG_0001.JPG.xmp")
+        .write_str(&packet("photoshop:DateCreated=\"2019-03-04T05:06:07\""))
+        .unwrap();
+
+    let mut cmd = createdat();
+
+    let output = cmd.current_dir(temp.path()).output().unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+
+    assert_eq!(
+        vec![
+            "IMG_0001-2019-03-04_05-06-07.JPG",
+            "IMG_0001-2019-03-04_05-06-07.JPG.xmp",
+            "edited-2021-05-06_07-08-09.jpg",
+            "scan-2020-02-03_04-05-06.tif",
+        ],
+        renamed_file_names(&temp.path().join("renamed"))
+    );
+    temp.close().unwrap();
+}
+
+#[test]
+fn image_metadata() {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let exif = tiff(
+        &[],
+        &[
+            ascii(0x9003, "2018:02:03 04:05:06"),
+            ascii(0x9011, "+05:30"),
+        ],
+        &[],
+    );
+    let mut jpeg = b"\xFF\xD8\xFF\xE1".to_vec();
+    jpeg.extend_from_slice(&(exif.len() as u16 + 8).to_be_bytes());
+    jpeg.extend_from_slice(b"Exif\0\0");
+    jpeg.extend_from_slice(&exif);
+    jpeg.extend_from_slice(b"\xFF\xDA");
+    temp.child("photo.jpg").write_binary(&jpeg).unwrap();
+
+    let png_header = b"\x89PNG\r\n\x1a\n".to_vec();
+    let mut screenshot = png_header.clone();
+    screenshot.extend(png_chunk(b"IHDR", &[0; 13]));
+    screenshot.extend(png_chunk(b"IDAT", &[0; 32]));
+    screenshot.extend(png_chunk(
+        b"tEXt",
+        b"Creation Time\0Sat, 17 Jul 2021 14:30:00 +0200",
+    ));
+    screenshot.extend(png_chunk(b"tIME", &[0x07, 0xE4, 1, 2, 3, 4, 5]));
+    screenshot.extend(png_chunk(b"IEND", &[]));
+    temp.child("screenshot.png")
+        .write_binary(&screenshot)
+        .unwrap();
+
+    let mut edited = png_header;
+    edited.extend(png_chunk(b"tIME", &[0x07, 0xE4, 1, 2, 3, 4, 5]));
+    edited.extend(png_chunk(b"IEND", &[]));
+    temp.child("edited.png").write_binary(&edited).unwrap();
+    let edited_formatted = DateTime::parse_from_rfc3339("2020-01-02T03:04:05Z")
+        .unwrap()
+        .with_timezone(&Local)
+        .format("%Y-%m-%d_%H-%M-%S");
+
+    let exif = tiff(&[], &[ascii(0x9003, "2017:06:07 08:09:10")], &[]);
+    let mut chunks = b"WEBP".to_vec();
+    chunks.extend(riff_chunk(b"VP8X", &[0; 10]));
+    chunks.extend(riff_chunk(b"EXIF", &exif));
+    let webp = riff_chunk(b"RIFF", &chunks);
+    temp.child("web.webp").write_binary(&webp).unwrap();
+
+    // APP1 segments with a length shorter than the length field, or longer than the file,
+    // fall back to the modified date
+    let broken_time = std::time::SystemTime::now() - std::time::Duration::from_secs(60 * 60);
+    let broken_formatted = DateTime::<Local>::from(broken_time).format("%Y-%m-%d_%H-%M-%S");
+    temp.child("empty.jpg")
+        .write_binary(b"\xFF\xD8\xFF\xE1\x00\x00Exif\x00\x00MM\x00*")
+        .unwrap();
+    temp.child("truncated.jpg")
+        .write_binary(b"\xFF\xD8\xFF\xE1\x10\x00Exif\x00\x00MM\x00*")
+        .unwrap();
+    set_modified(&temp.path().join("empty.jpg"), broken_time);
+    set_modified(&temp.path().join("truncated.jpg"), broken_time);
+
+    let mut cmd = createdat();
+
+    let output = cmd.current_dir(temp.path()).output().unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+
+    assert_eq!(
+        vec![
+            format!("edited-{}.png", edited_formatted),
+            format!("empty-{}.jpg", broken_formatted),
+            "photo-2018-02-03_04-05-06.jpg".to_owned(),
+            "screenshot-2021-07-17_14-30-00.png".to_owned(),
+            format!("truncated-{}.jpg", broken_formatted),
+            "web-2017-06-07_08-09-10.webp".to_owned(),
+        ],
+        renamed_file_names(&temp.path().join("renamed"))
+    );
+    temp.close().unwrap();
+}
+
+#[test]
+fn audio_metadata() {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let mut frame = b"TDRC".to_vec();
+    frame.extend_from_slice(&[0, 0, 0, 20, 0, 0, 3]);
+    frame.extend_from_slice(b"2019-08-09T10:11:12");
+    let mut mp3 = b"ID3\x04\0\0\0\0\0".to_vec();
+    mp3.push(frame.len() as u8 + 10);
+    mp3.extend(frame);
+    mp3.extend_from_slice(&[0; 10]);
+    mp3.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+    temp.child("memo.mp3").write_binary(&mp3).unwrap();
+
+    let comments = vorbis_comments(&["TITLE=Birds", "date=2018-01-02"]);
+    let mut flac = b"fLaC\0\0\0\x22".to_vec();
+    flac.extend_from_slice(&[0; 34]);
+    flac.push(0x84);
+    flac.extend_from_slice(&(comments.len() as u32).to_be_bytes()[1..]);
+    flac.extend(comments);
+    temp.child("field.flac").write_binary(&flac).unwrap();
+
+    let mut ogg = ogg_page(2, b"\x01vorbis\0\0\0\0\x01\x44\xac\0\0");
+    let mut comments = b"\x03vorbis".to_vec();
+    comments.extend(vorbis_comments(&["DATE=2017-05-06 07:08"]));
+    ogg.extend(ogg_page(0, &comments));
+    temp.child("note.ogg").write_binary(&ogg).unwrap();
+
+    let mut bext = vec![0; 320];
+    bext.extend_from_slice(b"2016-03-0405:06:07");
+    bext.extend_from_slice(&[0; 256]);
+    let mut wav = b"RIFF\0\0\0\0WAVE".to_vec();
+    wav.extend_from_slice(b"bext");
+    wav.extend_from_slice(&(bext.len() as u32).to_le_bytes());
+    wav.extend(bext);
+    temp.child("take.wav").write_binary(&wav).unwrap();
+
+    let mut value = vec![0, 0, 0, 1, 0, 0, 0, 0];
+    value.extend_from_slice(b"2015-10-11T12:13:14Z");
+    let ilst = atom(b"ilst", &atom(b"\xA9day", &atom(b"data", &value)));
+    let mut meta = vec![0; 4];
+    meta.extend(atom(b"hdlr", &[0; 25]));
+    meta.extend(ilst);
+    let mut m4a = atom(b"ftyp", b"M4A \0\0\0\0M4A isom");
+    m4a.extend(atom(b"moov", &atom(b"udta", &atom(b"meta", &meta))));
+    temp.child("voice.m4a").write_binary(&m4a).unwrap();
+    let voice_formatted = DateTime::parse_from_rfc3339("2015-10-11T12:13:14Z")
+        .unwrap()
+        .format("%Y-%m-%d_%H-%M-%S");
+
+    let mut cmd = createdat();
+
+    let output = cmd
+        .current_dir(temp.path())
+        .arg("--audio")
+        .output()
+        .unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+
+    assert_eq!(
+        vec![
+            "field-2018-01-02_00-00-00.flac".to_owned(),
+            "memo-2019-08-09_10-11-12.mp3".to_owned(),
+            "note-2017-05-06_07-08-00.ogg".to_owned(),
+            "take-2016-03-04_05-06-07.wav".to_owned(),
+            format!("voice-{}.m4a", voice_formatted),
+        ],
+        renamed_file_names(&temp.path().join("renamed"))
+    );
+    temp.close().unwrap();
+}
+
+#[test]
+fn document_metadata() {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    temp.child("report.pdf")
+        .write_str(
+            "%PDF-1.4\n1 0 obj\n<< /Title (Report) /CreationDate (D:20230405060708+02'00') >>\nendobj\ntrailer\n<< /Info 1 0 R >>\n%%EOF\n",
+        )
+        .unwrap();
+
+    // An indirect date first, the literal one in the info of a later revision
+    temp.child("revised.pdf")
+        .write_str(
+            "%PDF-1.4\n1 0 obj\n<< /CreationDate 12 0 R >>\nendobj\ntrailer\n<< /Info 1 0 R >>\n%%EOF\n\
+             2 0 obj\n<< /CreationDate (D:20200304050607) >>\nendobj\ntrailer\n<< /Info 2 0 R >>\n%%EOF\n",
+        )
+        .unwrap();
+    temp.child("hex.pdf")
+        .write_str(
+            "%PDF-1.4\n1 0 obj\n<< /CreationDate <443A3230313930323033303430353036> >>\nendobj\ntrailer\n<< /Info 1 0 R >>\n%%EOF\n",
+        )
+        .unwrap();
+
+    // XMP at the end of a large PDF without document info
+    let mut pdf = b"%PDF-1.7\n".to_vec();
+    pdf.resize(3 * 1024 * 1024, b' ');
+    pdf.extend_from_slice(
+        b"<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\
+          <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\
+          <rdf:Description rdf:about=\"\" xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\" \
+          xmp:CreateDate=\"2018-01-02T03:04:05\"/></rdf:RDF></x:xmpmeta>\n%%EOF\n",
+    );
+    temp.child("scan.pdf").write_binary(&pdf).unwrap();
+
+    // Stored like real files, `mimetype` has to be readable in the header
+    let write_zip = |name: &str, entries: &[(&str, &str)]| {
+        let file = std::fs::File::create(temp.path().join(name)).unwrap();
+        let mut zip = zip::ZipWriter::new(file);
+        for (entry, content) in entries {
+            zip.start_file(
+                *entry,
+                zip::write::SimpleFileOptions::default()
+                    .compression_method(zip::CompressionMethod::Stored),
+            )
+            .unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    };
+    write_zip(
+        "letter.docx",
+        &[
+            ("[Content_Types].xml", "<Types/>"),
+            ("word/document.xml", "<document/>"),
+            (
+                "docProps/core.xml",
+                r#"<cp:coreProperties xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties" xmlns:dcterms="http://purl.org/dc/terms/"><dcterms:created>2022-03-04T05:06:07</dcterms:created></cp:coreProperties>"#,
+            ),
+        ],
+    );
+    write_zip(
+        "notes.odt",
+        &[
+            ("mimetype", "application/vnd.oasis.opendocument.text"),
+            (
+                "meta.xml",
+                r#"<office:document-meta xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:meta="urn:oasis:names:tc:opendocument:xmlns:meta:1.0"><office:meta><meta:creation-date>2021-02-03T04:05:06.123456789</meta:creation-date></office:meta></office:document-meta>"#,
+            ),
+        ],
+    );
+
+    let mut cmd = createdat();
+
+    let output = cmd
+        .current_dir(temp.path())
+        .arg("--documents")
+        .output()
+        .unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+
+    assert_eq!(
+        vec![
+            "hex-2019-02-03_04-05-06.pdf".to_owned(),
+            "letter-2022-03-04_05-06-07.docx".to_owned(),
+            "notes-2021-02-03_04-05-06.odt".to_owned(),
+            "report-2023-04-05_06-07-08.pdf".to_owned(),
+            "revised-2020-03-04_05-06-07.pdf".to_owned(),
+            "scan-2018-01-02_03-04-05.pdf".to_owned(),
+        ],
+        renamed_file_names(&temp.path().join("renamed"))
+    );
+    temp.close().unwrap();
+}
+
+#[test]
+fn video_metadata() {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let recorded = DateTime::parse_from_rfc3339("2020-06-07T08:09:10Z").unwrap();
+    let millennium = DateTime::parse_from_rfc3339("2001-01-01T00:00:00Z").unwrap();
+    let nanoseconds = (recorded - millennium).num_nanoseconds().unwrap();
+    let mut mkv = vec![0x1A, 0x45, 0xDF, 0xA3, 0x8B, 0x42, 0x82, 0x88];
+    mkv.extend_from_slice(b"matroska");
+    // A segment of unknown size with the segment info in it
+    mkv.extend_from_slice(&[
+        0x18, 0x53, 0x80, 0x67, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+    ]);
+    mkv.extend_from_slice(&[0x15, 0x49, 0xA9, 0x66, 0x8B, 0x44, 0x61, 0x88]);
+    mkv.extend_from_slice(&nanoseconds.to_be_bytes());
+    temp.child("ride.mkv").write_binary(&mkv).unwrap();
+    let ride_formatted = recorded.with_timezone(&Local).format("%Y-%m-%d_%H-%M-%S");
+
+    let mut hdrl = b"hdrl".to_vec();
+    hdrl.extend(riff_chunk(b"avih", &[0; 56]));
+    hdrl.extend(riff_chunk(b"IDIT", b"Fri Mar 01 10:20:30 2019\n\0"));
+    let mut info = b"INFO".to_vec();
+    info.extend(riff_chunk(b"ICRD", b"2010-01-01\0"));
+    let mut avi = b"AVI ".to_vec();
+    avi.extend(riff_chunk(b"LIST", &hdrl));
+    avi.extend(riff_chunk(b"LIST", &info));
+    avi.extend(riff_chunk(b"LIST", b"movi"));
+    temp.child("dive.avi")
+        .write_binary(&riff_chunk(b"RIFF", &avi))
+        .unwrap();
+
+    // The local time and offset iPhones write, which wins over the movie header
+    let mut keys = vec![0, 0, 0, 0, 0, 0, 0, 1];
+    keys.extend(atom(b"mdta", b"com.apple.quicktime.creationdate"));
+    let mut value = vec![0, 0, 0, 1, 0, 0, 0, 0];
+    value.extend_from_slice(b"2021-05-06T07:08:09+0200");
+    let mut meta = atom(b"hdlr", &[0; 24]);
+    meta.extend(atom(b"keys", &keys));
+    meta.extend(atom(
+        b"ilst",
+        &atom(&1u32.to_be_bytes(), &atom(b"data", &value)),
+    ));
+    let created = DateTime::parse_from_rfc3339("2019-04-05T06:07:08Z").unwrap();
+    let epoch = DateTime::parse_from_rfc3339("1904-01-01T00:00:00Z").unwrap();
+    let mut mvhd = vec![0; 4];
+    mvhd.extend_from_slice(&((created - epoch).num_seconds() as u32).to_be_bytes());
+    mvhd.extend_from_slice(&[0; 92]);
+    let mut phone = atom(b"ftyp", b"qt  \0\0\0\0qt  ");
+    phone.extend(atom(
+        b"moov",
+        &[atom(b"mvhd", &mvhd), atom(b"meta", &meta)].concat(),
+    ));
+    temp.child("phone.mov").write_binary(&phone).unwrap();
+
+    let mut camera = atom(b"ftyp", b"isom\0\0\0\0isom");
+    camera.extend(atom(b"moov", &atom(b"mvhd", &mvhd)));
+    temp.child("camera.mp4").write_binary(&camera).unwrap();
+    let camera_formatted = created.with_timezone(&Local).format("%Y-%m-%d_%H-%M-%S");
+
+    // Without a creation time in the movie header, `©day` is used
+    let mut value = vec![0, 0, 0, 1, 0, 0, 0, 0];
+    value.extend_from_slice(b"2017-03-04T05:06:07Z");
+    let mut meta = vec![0; 4];
+    meta.extend(atom(b"hdlr", &[0; 25]));
+    meta.extend(atom(b"ilst", &atom(b"\xA9day", &atom(b"data", &value))));
+    let mut udta = atom(b"mvhd", &[0; 100]);
+    udta.extend(atom(b"udta", &atom(b"meta", &meta)));
+    let mut edit = atom(b"ftyp", b"mp42\0\0\0\0mp42isom");
+    edit.extend(atom(b"moov", &udta));
+    temp.child("edit.mp4").write_binary(&edit).unwrap();
+    let edit_formatted = DateTime::parse_from_rfc3339("2017-03-04T05:06:07Z")
+        .unwrap()
+        .format("%Y-%m-%d_%H-%M-%S");
+
+    let mut cmd = createdat();
+
+    let output = cmd
+        .current_dir(temp.path())
+        .arg("--videos")
+        .output()
+        .unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+
+    assert_eq!(
+        vec![
+            format!("camera-{}.mp4", camera_formatted),
+            "dive-2019-03-01_10-20-30.avi".to_owned(),
+            format!("edit-{}.mp4", edit_formatted),
+            "phone-2021-05-06_07-08-09.mov".to_owned(),
+            format!("ride-{}.mkv", ride_formatted),
+        ],
+        renamed_file_names(&temp.path().join("renamed"))
+    );
+    temp.close().unwrap();
+}
+
+/// Files with sizes that point anywhere fall back to the modified date without crashing
+#[test]
+fn broken_videos() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let modified =
+        std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_500_000_000);
+    let modified_formatted = DateTime::<Local>::from(modified).format("%Y-%m-%d_%H-%M-%S");
+
+    // A `free` atom with a 64 bit size that wraps the position around
+    let mut wrapping = atom(b"ftyp", b"isom\0\0\0\0");
+    wrapping.extend_from_slice(&1u32.to_be_bytes());
+    wrapping.extend_from_slice(b"free");
+    wrapping.extend_from_slice(&(u64::MAX - 7).to_be_bytes());
+    wrapping.resize(80, 0);
+    temp.child("wrapping.mp4").write_binary(&wrapping).unwrap();
+    set_modified(&temp.path().join("wrapping.mp4"), modified);
+
+    // Header lists nested until the stack would overflow
+    let date = riff_chunk(b"IDIT", b"Fri Mar 01 10:20:30 2019\n\0");
+    let levels = 200_000;
+    let mut avi = b"AVI ".to_vec();
+    for level in 0..levels {
+        // Each list holds its type, the lists inside it and the date
+        let length = 4 + 12 * (levels - 1 - level) + date.len() as u32;
+        avi.extend_from_slice(b"LIST");
+        avi.extend_from_slice(&length.to_le_bytes());
+        avi.extend_from_slice(b"hdrl");
+    }
+    avi.extend(date);
+    temp.child("nested.avi")
+        .write_binary(&riff_chunk(b"RIFF", &avi))
+        .unwrap();
+    set_modified(&temp.path().join("nested.avi"), modified);
+
+    let output = createdat()
+        .current_dir(temp.path())
+        .arg("--videos")
+        .output()
+        .unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        vec![
+            format!("nested-{modified_formatted}.avi"),
+            format!("wrapping-{modified_formatted}.mp4"),
+        ],
+        renamed_file_names(&temp.path().join("renamed"))
+    );
+    temp.close().unwrap();
+}
+
+#[test]
+fn metadata_placeholders() {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let tiff = tiff(
+        &[ascii(0x010F, "Canon"), ascii(0x0110, "Canon EOS R5")],
+        &[
+            ascii(0x9003, "2022:01:02 03:04:05"),
+            short(0x8827, 400),
+            long(0xA002, 6000),
+            long(0xA003, 4000),
+            ascii(0xA434, "RF24-105mm F4L"),
+        ],
+        &[],
+    );
+    temp.child("shot.tif").write_binary(&tiff).unwrap();
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    let mut ihdr = 640u32.to_be_bytes().to_vec();
+    ihdr.extend_from_slice(&480u32.to_be_bytes());
+    ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+    png.extend(png_chunk(b"IHDR", &ihdr));
+    png.extend(png_chunk(b"eXIf", &tiff));
+    png.extend(png_chunk(b"IEND", &[]));
+    temp.child("export.png").write_binary(&png).unwrap();
+
+    let recorded = DateTime::parse_from_rfc3339("2020-06-07T12:00:00Z").unwrap();
+    let millennium = DateTime::parse_from_rfc3339("2001-01-01T00:00:00Z").unwrap();
+    let nanoseconds = (recorded - millennium).num_nanoseconds().unwrap();
+    let mut mkv = vec![0x1A, 0x45, 0xDF, 0xA3, 0x8B, 0x42, 0x82, 0x88];
+    mkv.extend_from_slice(b"matroska");
+    mkv.extend_from_slice(&[
+        0x18, 0x53, 0x80, 0x67, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+    ]);
+    // Segment info with the date and a duration of 83.5 seconds in milliseconds
+    mkv.extend_from_slice(&[0x15, 0x49, 0xA9, 0x66, 0x96, 0x44, 0x61, 0x88]);
+    mkv.extend_from_slice(&nanoseconds.to_be_bytes());
+    mkv.extend_from_slice(&[0x44, 0x89, 0x88]);
+    mkv.extend_from_slice(&83500f64.to_be_bytes());
+    // A track entry with a 1920x1080 picture
+    mkv.extend_from_slice(&[0x16, 0x54, 0xAE, 0x6B, 0x8C, 0xAE, 0x8A, 0xE0, 0x88]);
+    mkv.extend_from_slice(&[0xB0, 0x82, 0x07, 0x80, 0xBA, 0x82, 0x04, 0x38]);
+    temp.child("clip.mkv").write_binary(&mkv).unwrap();
+    let clip_formatted = recorded.with_timezone(&Local).format("%Y-%m-%d");
+
+    // A version 1 movie header with a timescale of one and an unknown duration
+    let mut mvhd = vec![1, 0, 0, 0];
+    mvhd.extend_from_slice(&[0; 16]);
+    mvhd.extend_from_slice(&1u32.to_be_bytes());
+    mvhd.extend_from_slice(&u64::MAX.to_be_bytes());
+    let mut mov = atom(b"ftyp", b"qt  \0\0\0\0qt  ");
+    mov.extend(atom(b"moov", &atom(b"mvhd", &mvhd)));
+    temp.child("stream.mov").write_binary(&mov).unwrap();
+    let stream_time = std::time::SystemTime::now() - std::time::Duration::from_secs(60 * 60);
+    set_modified(&temp.path().join("stream.mov"), stream_time);
+    let stream_formatted = DateTime::<Local>::from(stream_time).format("%Y-%m-%d");
+
+    let mut cmd = createdat();
+
+    let output = cmd
+        .current_dir(temp.path())
+        .args([
+            "--images",
+            "--videos",
+            "--format",
+            "%Y-%m-%d {model} ISO{iso} {width}x{height} {duration} {lens}",
+            "--folders",
+            "{make}",
+            "--missing",
+            "none",
+        ])
+        .output()
+        .unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+
+    let renamed = temp.path().join("renamed");
+    assert_eq!(
+        vec![
+            "export-2022-01-02 Canon EOS R5 ISO400 640x480 none RF24-105mm F4L.png",
+            "shot-2022-01-02 Canon EOS R5 ISO400 6000x4000 none RF24-105mm F4L.tif",
+        ],
+        renamed_file_names(&renamed.join("Canon"))
+    );
+    assert_eq!(
+        vec![
+            format!("clip-{clip_formatted} none ISOnone 1920x1080 1m24s none.mkv"),
+            format!("stream-{stream_formatted} none ISOnone nonexnone none none.mov"),
+        ],
+        renamed_file_names(&renamed.join("none"))
+    );
+    temp.close().unwrap();
+}
+
+#[test]
+fn location_placeholders() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    temp.child("tram.tif")
+        .write_binary(&tiff(
+            &[ascii(0x0132, "2022:01:02 03:04:05")],
+            &[],
+            &[
+                ascii(0x0001, "N"),
+                rationals(0x0002, &[38, 43, 0]),
+                ascii(0x0003, "W"),
+                rationals(0x0004, &[9, 8, 0]),
+            ],
+        ))
+        .unwrap();
+    // In the middle of the Atlantic, far from every city
+    temp.child("ocean.tif")
+        .write_binary(&tiff(
+            &[ascii(0x0132, "2022:02:03 04:05:06")],
+            &[],
+            &[
+                ascii(0x0001, "N"),
+                rationals(0x0002, &[30, 0, 0]),
+                ascii(0x0003, "W"),
+                rationals(0x0004, &[40, 0, 0]),
+            ],
+        ))
+        .unwrap();
+
+    let args = [
+        "--images",
+        "--format",
+        "%Y-%m-%d {country} {lat} {lon}",
+        "--folders",
+        "%Y {city}",
+        "--missing",
+        "none",
+    ];
+    let mut cmd = createdat();
+    let output = cmd.current_dir(temp.path()).args(args).output().unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+
+    let renamed = temp.path().join("renamed");
+    assert_eq!(
+        vec!["tram-2022-01-02 Portugal 38.7167 -9.1333.tif"],
+        renamed_file_names(&renamed.join("2022 Lisbon"))
+    );
+    assert_eq!(
+        vec!["ocean-2022-02-03 none 30.0000 -40.0000.tif"],
+        renamed_file_names(&renamed.join("2022 none"))
+    );
+
+    // A GeoNames file with a town closer than Lisbon
+    temp.child("cities.txt")
+        .write_str("2271071\tAlmada\tAlmada\t\t38.67904\t-9.15694\tP\tPPLA2\tPT\n")
+        .unwrap();
+    let mut cmd = createdat();
+    let output = cmd
+        .current_dir(temp.path())
+        .args(args)
+        .args(["--places", "cities.txt", "--target", "almada"])
+        .output()
+        .unwrap();
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+
+    assert_eq!(
+        vec!["tram-2022-01-02 Portugal 38.7167 -9.1333.tif"],
+        renamed_file_names(&temp.path().join("almada/2022 Almada"))
+    );
+
+    // Files that aren't GeoNames files are reported instead of finding no cities
+    temp.child("empty.txt").touch().unwrap();
+    let mut cmd = createdat();
+    cmd.current_dir(temp.path())
+        .args(args)
+        .args(["--places", "empty.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no cities found"));
+
+    temp.close().unwrap();
+}
+
+#[test]
+fn jobs() {
+    let (temp, now_formatted) = setup();
+    temp.child("a.jpg").touch().unwrap();
+
+    let mut cmd = createdat();
+
+    let output = cmd
+        .current_dir(temp.path())
+        .args(["-a", "-p", "-j", "1"])
+        .output()
+        .unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+
+    let renamed = std::path::Path::new("renamed");
+    let expected: String = [
+        format!("{}.gitignore", now_formatted),
+        format!("a-{}.jpg", now_formatted),
+        format!("test-{}.jpg", now_formatted),
+        format!("test-{}.mp4", now_formatted),
+    ]
+    .iter()
+    .map(|name| format!("{}\n", renamed.join(name).display()))
+    .collect();
+    assert_eq!(expected, String::from_utf8(output.stdout).unwrap());
+
+    let mut cmd = createdat();
+    cmd.current_dir(temp.path())
+        .args(["--jobs", "4", "--metadata-jobs", "2", "--copy-jobs", "1"])
+        .assert()
+        .success();
+    assert_eq!(
+        vec![
+            format!("a-{}.jpg", now_formatted),
+            format!("test-{}.jpg", now_formatted)
+        ],
+        renamed_file_names(&temp.path().join("renamed"))
+    );
+    temp.close().unwrap();
+}
+
+#[tokio::test]
+async fn progress_events() {
+    let (temp, _) = setup();
+    temp.child("test.jpg").write_binary(PNG_HEADER).unwrap();
+    temp.child("notes.txt").touch().unwrap();
+
+    let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let listener_events = events.clone();
+    let options = createdat::RenameOptions::new(temp.path())
+        .target(temp.path().join("renamed"))
+        .progress(move |event: &createdat::RenameEvent| {
+            let event = match event {
+                createdat::RenameEvent::Planned { source, size, .. } => {
+                    format!(
+                        "planned {} {}",
+                        source.file_name().unwrap().to_string_lossy(),
+                        size
+                    )
+                }
+                createdat::RenameEvent::Copied { source, bytes, .. } => {
+                    format!(
+                        "copied {} {}",
+                        source.file_name().unwrap().to_string_lossy(),
+                        bytes
+                    )
+                }
+                createdat::RenameEvent::Skipped { path, reason } => {
+                    format!(
+                        "skipped {} {:?}",
+                        path.file_name().unwrap().to_string_lossy(),
+                        reason
+                    )
+                }
+                createdat::RenameEvent::Failed { path, .. } => {
+                    format!("failed {}", path.file_name().unwrap().to_string_lossy())
+                }
+            };
+            listener_events.lock().unwrap().push(event);
+        });
+    createdat::Renamer::new(options).run().await.unwrap();
+
+    let mut events = events.lock().unwrap().clone();
+    events.sort();
+    let size = PNG_HEADER.len();
+    assert_eq!(
+        vec![
+            format!("copied test.jpg {size}"),
+            format!("planned test.jpg {size}"),
+            "skipped .gitignore Category".to_owned(),
+            "skipped notes.txt Category".to_owned(),
+            "skipped test.mp4 Category".to_owned(),
+        ],
+        events
+    );
+    temp.close().unwrap();
+}
+
+/// Benchmark, run with `cargo test --release -- --ignored --nocapture bench_100k_files`
+#[tokio::test(flavor = "multi_thread")]
+#[ignore]
+async fn bench_100k_files() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    for i in 0..100_000 {
+        temp.child(format!("file-{i}.jpg")).touch().unwrap();
+    }
+
+    let start = std::time::Instant::now();
+    let options = createdat::RenameOptions::new(temp.path()).target(temp.path().join("renamed"));
+    let report = createdat::Renamer::new(options).run().await.unwrap();
+    println!("Renamed 100000 files in {:?}", start.elapsed());
+    // Peak memory of the test process, on Linux
+    if let Ok(status) = std::fs::read_to_string("/proc/self/status") {
+        if let Some(peak) = status.lines().find(|line| line.starts_with("VmHWM")) {
+            println!("{peak}");
+        }
+    }
+
+    assert_eq!(100_000, report.count.renamed);
+    temp.close().unwrap();
+}
+
+#[test]
+fn config() {
+    let (temp, now_formatted) = setup();
+    temp.child("config")
+        .child("createdat")
+        .child("config.toml")
+        .write_str(
+            "name = \"global\"\nall = true\n\n[profile.phone]\nname = \"phone\"\nfront = true\n",
+        )
+        .unwrap();
+    temp.child(".createdat.toml")
+        .write_str("target = \"local\"\nexclude = [\".*\"]\n\n[profile.phone]\nsuffix = true\n")
+        .unwrap();
+
+    let mut cmd = createdat();
+    cmd.current_dir(temp.path())
+        .env("XDG_CONFIG_HOME", temp.path().join("config"))
+        .assert()
+        .success();
+    assert_eq!(
+        vec![
+            format!("global-test-{}.jpg", now_formatted),
+            format!("global-test-{}.mp4", now_formatted),
+        ],
+        renamed_file_names(&temp.path().join("local"))
+    );
+
+    let mut cmd = createdat();
+    cmd.current_dir(temp.path())
+        .env("XDG_CONFIG_HOME", temp.path().join("config"))
+        .args(["--profile", "phone", "-T", "cli"])
+        .assert()
+        .success();
+    assert_eq!(
+        vec![
+            format!("{}-test-phone.jpg", now_formatted),
+            format!("{}-test-phone.mp4", now_formatted),
+        ],
+        renamed_file_names(&temp.path().join("cli"))
+    );
+
+    // Flags turned on in a config file are turned off for one run
+    let mut cmd = createdat();
+    cmd.current_dir(temp.path())
+        .env("XDG_CONFIG_HOME", temp.path().join("config"))
+        .args([
+            "--profile",
+            "phone",
+            "-T",
+            "negated",
+            "--no-front",
+            "--no-suffix",
+        ])
+        .assert()
+        .success();
+    assert_eq!(
+        vec![
+            format!("phone-test-{}.jpg", now_formatted),
+            format!("phone-test-{}.mp4", now_formatted),
+        ],
+        renamed_file_names(&temp.path().join("negated"))
+    );
+
+    let mut cmd = createdat();
+    cmd.current_dir(temp.path())
+        .env("XDG_CONFIG_HOME", temp.path().join("config"))
+        .args(["--profile", "tablet"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("profile 'tablet' was not found"));
+    temp.close().unwrap();
+}
+
+#[test]
+fn env() {
+    let (temp, now_formatted) = setup();
+    temp.child(".createdat.toml")
+        .write_str("target = \"local\"\nfront = true\nname = \"config\"\n")
+        .unwrap();
+
+    let mut cmd = createdat();
+    cmd.current_dir(temp.path())
+        .env("XDG_CONFIG_HOME", temp.path().join("config"))
+        .env("CREATEDAT_TARGET", "env")
+        .env("CREATEDAT_FRONT", "false")
+        .env("CREATEDAT_EXT", "jpg,mp4")
+        .assert()
+        .success();
+    assert_eq!(
+        vec![
+            format!("config-test-{}.jpg", now_formatted),
+            format!("config-test-{}.mp4", now_formatted),
+        ],
+        renamed_file_names(&temp.path().join("env"))
+    );
+
+    let mut cmd = createdat();
+    cmd.current_dir(temp.path())
+        .env("XDG_CONFIG_HOME", temp.path().join("config"))
+        .env("CREATEDAT_TARGET", "env")
+        .env("CREATEDAT_NAME", "env")
+        .args(["-T", "cli"])
+        .assert()
+        .success();
+    assert_eq!(
+        vec![format!("{}-env-test.jpg", now_formatted)],
+        renamed_file_names(&temp.path().join("cli"))
+    );
+    temp.close().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn watch() {
+    use std::{
+        process::{Command as Process, Stdio},
+        thread::sleep,
+        time::{Duration, Instant},
+    };
+
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source = temp.child("scans");
+    source.create_dir_all().unwrap();
+
+    let watcher = Process::new(assert_cmd::cargo::cargo_bin("createdat"))
+        .current_dir(temp.path())
+        .env("XDG_CONFIG_HOME", temp.path().join("config"))
+        .args(["-S", "scans", "-T", "renamed", "--ext", "txt", "watch"])
+        .args(["--settle", "10000", "--debounce", "100"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    sleep(Duration::from_millis(500));
+
+    source.child("first.txt").write_str("first").unwrap();
+    let start = Instant::now();
+    while renamed_file_names(&temp.path().join("renamed")).is_empty()
+        && start.elapsed() < Duration::from_secs(10)
+    {
+        sleep(Duration::from_millis(50));
+    }
+    assert_eq!(1, renamed_file_names(&temp.path().join("renamed")).len());
+
+    // Still within the debounce time when the watcher is stopped, renamed on shutdown
+    source.child("second.txt").write_str("second").unwrap();
+    // Still open without growing, so it only had a single event
+    let mut third = std::fs::File::create(source.child("third.txt").path()).unwrap();
+    third.write_all(b"third").unwrap();
+    sleep(Duration::from_millis(20));
+    Process::new("kill")
+        .args(["-INT", &watcher.id().to_string()])
+        .status()
+        .unwrap();
+    let output = watcher.wait_with_output().unwrap();
+    drop(third);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Files renamed while watching"));
+
+    let names = renamed_file_names(&temp.path().join("renamed"));
+    assert_eq!(3, names.len());
+    assert!(names[0].starts_with("first-") && names[1].starts_with("second-"));
+    assert!(names[2].starts_with("third-"));
+    temp.close().unwrap();
+}
+
+#[test]
+fn watch_nested_target() {
+    use std::{
+        process::{Command as Process, Stdio},
+        thread::sleep,
+        time::{Duration, Instant},
+    };
+
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source = temp.child("scans");
+    source.create_dir_all().unwrap();
+
+    // The target folder is inside the watched folder, its copies aren't renamed again
+    let watcher = Process::new(assert_cmd::cargo::cargo_bin("createdat"))
+        .current_dir(source.path())
+        .env("XDG_CONFIG_HOME", temp.path().join("config"))
+        .args([
+            "--ext",
+            "txt",
+            "watch",
+            "--settle",
+            "200",
+            "--debounce",
+            "50",
+        ])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    sleep(Duration::from_millis(500));
+
+    source.child("page.txt").write_str("page").unwrap();
+    let renamed = source.path().join("renamed");
+    let start = Instant::now();
+    while renamed_file_names(&renamed).is_empty() && start.elapsed() < Duration::from_secs(10) {
+        sleep(Duration::from_millis(50));
+    }
+    sleep(Duration::from_millis(500));
+    Process::new("kill")
+        .args(["-INT", &watcher.id().to_string()])
+        .status()
+        .unwrap();
+    let output = watcher.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let names = renamed_file_names(&renamed);
+    assert_eq!(1, names.len());
+    assert!(names[0].starts_with("page-"));
+
+    // A source folder inside the target folder is refused
+    let mut cmd = createdat();
+    cmd.current_dir(source.path())
+        .args(["-T", "..", "watch"])
+        .timeout(Duration::from_secs(10))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "can't be the watched source folder",
+        ));
+    temp.close().unwrap();
+}
+
+#[test]
+fn import() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let card = temp.child("card");
+    card.child("DCIM")
+        .child("100TEST")
+        .child("IMG_0001.JPG")
+        .write_binary(PNG_HEADER)
+        .unwrap();
+    card.child("DCIM")
+        .child("101TEST")
+        .child("IMG_0002.JPG")
+        .write_binary(PNG_HEADER)
+        .unwrap();
+    let now_local: DateTime<Local> = Utc::now().into();
+    let day_folder = temp
+        .path()
+        .join("library")
+        .join(now_local.format("%Y/%Y-%m-%d").to_string());
+
+    let mut cmd = createdat();
+    cmd.current_dir(temp.path())
+        .env("XDG_CONFIG_HOME", temp.path().join("config"))
+        .args(["-T", "library", "import", "card"])
+        .assert()
+        .success();
+    let imported = renamed_file_names(&day_folder);
+    assert_eq!(2, imported.len());
+    assert!(imported[0].starts_with("IMG_0001-") && imported[1].starts_with("IMG_0002-"));
+
+    // Already imported files are neither copied again nor reported as existing
+    card.child("DCIM")
+        .child("101TEST")
+        .child("IMG_0003.JPG")
+        .write_binary(PNG_HEADER)
+        .unwrap();
+    let mut cmd = createdat();
+    cmd.current_dir(temp.path())
+        .env("XDG_CONFIG_HOME", temp.path().join("config"))
+        .args(["-T", "library", "import", "card", "--clear"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("already exists").not());
+    assert_eq!(3, renamed_file_names(&day_folder).len());
+    assert!(renamed_file_names(&card.path().join("DCIM").join("100TEST")).is_empty());
+    assert!(renamed_file_names(&card.path().join("DCIM").join("101TEST")).is_empty());
+
+    // Profiles apply to the import options too, and can be turned off for one run
+    temp.child(".createdat.toml")
+        .write_str("[profile.card]\nclear = true\n")
+        .unwrap();
+    let new_file = card.child("DCIM").child("102TEST").child("IMG_0004.JPG");
+    new_file.write_binary(PNG_HEADER).unwrap();
+    let mut cmd = createdat();
+    cmd.current_dir(temp.path())
+        .env("XDG_CONFIG_HOME", temp.path().join("config"))
+        .args([
+            "-T",
+            "library",
+            "--profile",
+            "card",
+            "import",
+            "card",
+            "--no-clear",
+        ])
+        .assert()
+        .success();
+    assert_eq!(4, renamed_file_names(&day_folder).len());
+    assert!(new_file.path().exists());
+
+    // Originals are never deleted because of an environment variable
+    let mut cmd = createdat();
+    cmd.current_dir(temp.path())
+        .env("XDG_CONFIG_HOME", temp.path().join("config"))
+        .env("CREATEDAT_CLEAR", "true")
+        .env("CREATEDAT_IMPORT_CLEAR", "true")
+        .args(["-T", "library", "import", "card"])
+        .assert()
+        .success();
+    assert!(new_file.path().exists());
+    let mut cmd = createdat();
+    cmd.current_dir(temp.path())
+        .env("XDG_CONFIG_HOME", temp.path().join("config"))
+        .args(["-T", "library", "--profile", "card", "import", "card"])
+        .assert()
+        .success();
+    assert!(!new_file.path().exists());
+
+    // Folder formats chrono can't write are rejected before anything is copied
+    let mut cmd = createdat();
+    cmd.current_dir(temp.path())
+        .env("XDG_CONFIG_HOME", temp.path().join("config"))
+        .args(["-T", "library", "--folders", "%Y/%Q", "import", "card"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid date format \"%Y/%Q\""));
+    temp.close().unwrap();
+}